@@ -0,0 +1,197 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use octocrab::service::middleware::{auth_header::AuthHeaderLayer, base_uri::BaseUriLayer};
+use reqwest::{header::RETRY_AFTER, StatusCode};
+
+use crate::USER_AGENT;
+
+const GITHUB_API: &str = "https://api.github.com";
+const RETRIES: u32 = 5;
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Routes octocrab requests through a [`reqwest::Client`],
+/// so that its configuration (proxy etc.) applies to github too
+#[derive(Clone)]
+struct ReqwestService(reqwest::Client);
+
+impl tower::Service<http::Request<String>> for ReqwestService {
+    type Response = http::Response<reqwest::Body>;
+    type Error = reqwest::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<String>) -> Self::Future {
+        let client = self.0.clone();
+        Box::pin(async move {
+            let req = reqwest::Request::try_from(req)?;
+            Ok(execute(&client, req).await?.into())
+        })
+    }
+}
+
+/// Sends a GET request, see [`execute`]
+pub async fn get(client: &reqwest::Client, url: &str) -> reqwest::Result<reqwest::Response> {
+    execute(client, client.get(url).build()?).await
+}
+
+/// Executes a request, retrying transient failures with growing backoff:
+/// connection errors, timeouts, server errors and rate limits with `Retry-After`
+pub async fn execute(
+    client: &reqwest::Client,
+    req: reqwest::Request,
+) -> reqwest::Result<reqwest::Response> {
+    for attempt in 0..RETRIES {
+        // requests with streamed bodies can't be repeated
+        let Some(attempt_req) = req.try_clone() else {
+            break;
+        };
+        let backoff = RETRY_BACKOFF * 2u32.pow(attempt);
+
+        match client.execute(attempt_req).await {
+            Ok(res) => match rate_limit(&res) {
+                Some(wait) => {
+                    status!(
+                        "Honouring rate limit of {}, retrying in {}s...",
+                        host(&res),
+                        wait.as_secs()
+                    );
+                    tokio::time::sleep(wait).await;
+                }
+                None if res.status().is_server_error() => {
+                    status!(
+                        "{} responded with {}, retrying in {:.1}s...",
+                        host(&res),
+                        res.status(),
+                        backoff.as_secs_f64()
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                None => return Ok(res),
+            },
+            Err(e) if e.is_timeout() || e.is_connect() => {
+                status!("{e}, retrying in {:.1}s...", backoff.as_secs_f64());
+                tokio::time::sleep(backoff).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    client.execute(req).await
+}
+
+fn host(res: &reqwest::Response) -> &str {
+    res.url().host_str().unwrap_or_default()
+}
+
+/// Wait requested when a rate limit is hit, e.g. github's secondary rate limits,
+/// these come with `Retry-After` even when core quota remains
+fn rate_limit(res: &reqwest::Response) -> Option<Duration> {
+    if !matches!(
+        res.status(),
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+    ) {
+        return None;
+    }
+
+    res.headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Client shared by all sources and crates.io,
+/// honours HTTP_PROXY/HTTPS_PROXY env unless proxy is provided
+pub fn http_client(
+    proxy: Option<&str>,
+    timeout: Option<Duration>,
+) -> anyhow::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder().user_agent(USER_AGENT);
+
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Github client over the shared client, talking to api.github.com unless given another api
+pub fn github_client(
+    client: reqwest::Client,
+    token: Option<String>,
+    api: Option<&str>,
+) -> anyhow::Result<octocrab::Octocrab> {
+    let uri = match api {
+        Some(api) => http::Uri::try_from(api)?,
+        None => http::Uri::from_static(GITHUB_API),
+    };
+    let auth_header = token
+        .map(|tok| format!("Bearer {tok}").parse())
+        .transpose()?;
+
+    Ok(octocrab::OctocrabBuilder::new_empty()
+        .with_service(ReqwestService(client))
+        .with_layer(&BaseUriLayer::new(uri.clone()))
+        .with_layer(&AuthHeaderLayer::new(auth_header, uri))
+        .with_auth(octocrab::AuthState::None)
+        .build()?)
+}
+
+/// Verifies a github token before fetching begins, failing if github rejects it
+/// and warning about scopes too narrow for private repositories
+pub async fn check_github_token(client: &octocrab::Octocrab, token: &str) -> anyhow::Result<()> {
+    // being offline isn't the token's fault, cached data may do
+    let res = match client._get("/user").await {
+        Ok(res) => res,
+        Err(e) => {
+            let e = e.to_string();
+            // octocrab appends a backtrace
+            let e = e.lines().next().unwrap_or_default();
+            warn!("couldn't verify the github access token: {e}");
+            return Ok(());
+        }
+    };
+
+    if res.status() == StatusCode::UNAUTHORIZED {
+        anyhow::bail!("github rejected the access token, it may be mistyped, expired or revoked");
+    }
+
+    let header = |name: &str| {
+        res.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+
+    // classic tokens list their scopes, fine-grained ones don't
+    match header("x-oauth-scopes") {
+        Some(scopes) if !scopes.split(',').any(|s| s.trim() == "repo") => warn!(
+            "the github access token lacks the repo scope, contributors of private repositories can't be read (scopes: {})",
+            if scopes.trim().is_empty() { "none" } else { scopes.trim() }
+        ),
+        None if token.starts_with("github_pat_") => status!(
+            "using a fine-grained github access token, private repositories need read access to their contents and metadata"
+        ),
+        _ => {}
+    }
+
+    if let Some(expiration) = header("github-authentication-token-expiration") {
+        status!("the github access token expires {expiration}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tokio::time::{sleep, Duration};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Turns the first Ctrl-C into a request to stop fetching,
+/// so that data fetched so far is cached, a second one exits right away.
+/// Only on unix, elsewhere Ctrl-C exits right away as before
+pub fn install() {
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as *const () as libc::sighandler_t,
+        );
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: libc::c_int) {
+    // only async-signal-safe calls are allowed here
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        unsafe { libc::_exit(130) };
+    }
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Resolves once interrupted
+pub async fn wait() {
+    while !interrupted() {
+        sleep(POLL_INTERVAL).await;
+    }
+}
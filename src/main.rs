@@ -1,27 +1,124 @@
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap, HashSet},
-    path::PathBuf,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex, Once, OnceLock,
+    },
+    time::{SystemTime, UNIX_EPOCH},
 };
 
+use anstyle::{AnsiColor, Effects, Style};
 use cargo_toml::{Dependency, Manifest};
 use clap::{Parser, Subcommand};
-use handlebars::Handlebars;
-use octocrab::models::RateLimit;
+use handlebars::{DirectorySourceOptions, Handlebars};
 use serde::{Deserialize, Serialize};
+use sources::{
+    ContributorSource, GitHub, GitHubGraphQl, GitHubUsers, GitLab, LocalRepos, Resolver, SourceHut,
+    WaitExceeded,
+};
 use tokio::{
     fs,
-    sync::mpsc::unbounded_channel,
-    time::{sleep, sleep_until, Duration, Instant},
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+    time::{sleep_until, Duration, Instant},
 };
-use unfmt_macros::unformat;
+use unicode_normalization::UnicodeNormalization;
+
+/// Styles of progress, colors are stripped with NO_COLOR or when not writing to a terminal
+const CACHED: Style = Style::new().effects(Effects::DIMMED);
+const FETCHING: Style = AnsiColor::Cyan.on_default();
+const WARNING: Style = AnsiColor::Yellow.on_default();
+const ERROR: Style = AnsiColor::Red.on_default().effects(Effects::BOLD);
+const WAITING: Style = AnsiColor::Magenta.on_default();
+
+/// Progress is reported on stderr while the output itself goes to stdout
+static STATUS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Counted for the summary at the end of a run
+static CACHE_HITS: AtomicUsize = AtomicUsize::new(0);
+static FETCHES: AtomicUsize = AtomicUsize::new(0);
+static EMPTY_SOURCES: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Seconds spent in each phase and source, printed with --timing
+static TIMINGS: Mutex<Vec<(String, f64)>> = Mutex::new(Vec::new());
+
+/// Github sources left unfetched once --max-wait is used up
+static WAIT_EXCEEDED: AtomicUsize = AtomicUsize::new(0);
+
+/// Ambiguous sources are prompted for with --interactive on a terminal
+static INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Overrides the platform's cache directory
+static CACHE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Entries older than this are treated as missing
+static MAX_CACHE_AGE: OnceLock<Duration> = OnceLock::new();
+
+/// Manifest piped in with `--path -`, read once as it's needed more than once
+static STDIN_MANIFEST: OnceLock<String> = OnceLock::new();
+
+macro_rules! status {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        if $crate::STATUS_TO_STDERR.load(std::sync::atomic::Ordering::Relaxed) {
+            _ = writeln!(anstream::stderr(), $($arg)*);
+        } else {
+            _ = writeln!(anstream::stdout(), $($arg)*);
+        }
+    }};
+}
+
+/// Warnings stand out from progress
+macro_rules! warn {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        _ = writeln!(
+            anstream::stderr(),
+            "{}{}{:#}",
+            $crate::WARNING,
+            format_args!($($arg)*),
+            $crate::WARNING
+        );
+    }};
+}
+
+mod client;
+mod interrupt;
+mod sources;
 
 const USER_AGENT: &str = "acknowledgments.rs (acknowledgements_rs@proton.me)";
+const CRATES_IO_API: &str = "https://crates.io/api/v1";
 const CRATES_IO_RATE_LIMIT: u64 = 1000;
 const GITHUB_BASE: &str = "https://github.com";
-const GITHUB_AT_GIT: &str = "git@github.com";
+const NPM_REGISTRY: &str = "https://registry.npmjs.org";
+const RUST_REPOSITORY: &str = "https://github.com/rust-lang/rust";
+const SOURCEHUT_BASES: [&str; 2] = ["https://git.sr.ht", "https://hg.sr.ht"];
+const ALLOWED_HOSTS: [&str; 2] = ["github.com", "gitlab.com"];
 const TEMPLATE: &str = include_str!("./template.md");
+const TEMPLATE_RST: &str = include_str!("./template.rst");
 const CACHE_NAME: &str = "acknowledgements_cache";
+/// Prefixes every cache entry, bump whenever the shape of cached data changes
+const CACHE_SCHEMA_VERSION: u8 = 5;
+const TOKEN_CACHE_KEY: &str = "github_access_token";
+const PROCESSED_SOURCES_KEY: &str = "processed_sources";
+/// How long a permanently failed source is skipped, unless max cache age is shorter
+const FAILURE_CACHE_AGE: Duration = Duration::from_secs(24 * 60 * 60);
 const FILE_NAME: &str = "ACKNOWLEDGEMENTS.md";
+const LICENSES_FILE_NAME: &str = "LICENSES.md";
+const ALL_CONTRIBUTORS_FILE_NAME: &str = ".all-contributorsrc";
+/// Category of crates without one on crates.io
+const UNCATEGORIZED: &str = "Other";
+const BEGIN_MARKER: &str = "<!-- acknowledge:begin -->";
+const END_MARKER: &str = "<!-- acknowledge:end -->";
+/// Nouns the plural helper doesn't inflect by suffix, in lowercase
+const IRREGULAR_PLURALS: &[(&str, &str)] = &[
+    ("person", "people"),
+    ("child", "children"),
+    ("man", "men"),
+    ("woman", "women"),
+];
 
 /// acknowledge is a simple CLI tool
 /// to analyze dependencies of a Cargo (rust) project
@@ -30,17 +127,29 @@ const FILE_NAME: &str = "ACKNOWLEDGEMENTS.md";
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Path to Cargo project for analysis
-    #[arg(short, long)]
-    path: PathBuf,
+    /// Path to Cargo project for analysis, repeat to acknowledge several projects together, - reads Cargo.toml from stdin
+    #[arg(short, long, required = true)]
+    path: Vec<PathBuf>,
 
     /// Running Acknowledgements on any project of reasonable size you're likely to face rate limits. Please provide a personal access token.
     #[arg(short, long)]
     gh_token: Option<String>,
 
-    /// Output file path, defaults to project path if not provided
+    /// Base url of the github api, e.g. of a stub server in tests
+    #[arg(long, env = "ACKNOWLEDGE_GITHUB_API", hide = true)]
+    github_api: Option<String>,
+
+    /// Base url of the crates.io api, e.g. of a stub server in tests
+    #[arg(long, env = "ACKNOWLEDGE_CRATES_IO_API", hide = true)]
+    crates_io_api: Option<String>,
+
+    /// Base url of gitlab instances, e.g. of a stub server in tests
+    #[arg(long, env = "ACKNOWLEDGE_GITLAB_API", hide = true)]
+    gitlab_api: Option<String>,
+
+    /// Output file path, defaults to project path if not provided, use - to write to stdout, repeat as Format=path to write several formats at once
     #[arg(short, long)]
-    output: Option<PathBuf>,
+    output: Vec<PathBuf>,
 
     /// Whether to include @ (at) symbol in front of a github user's name
     #[arg(short, long, default_value_t = false)]
@@ -50,6 +159,10 @@ struct Args {
     #[arg(short, long, default_value_t = Format::NameAndCount)]
     format: Format,
 
+    /// Markup of the bundled template, output files ending with .rst are always rendered as Rst
+    #[arg(long, default_value_t = Markup::Markdown)]
+    markup: Markup,
+
     /// Breadth of scan, whether to include optional, build and dev deps contributors
     #[arg(short, long, default_value_t = Breadth::NonOpt)]
     breadth: Breadth,
@@ -58,24 +171,305 @@ struct Args {
     #[arg(short, long, default_value_t = 2)]
     contributions_threshold: usize,
 
+    /// How contributions threshold is applied, as a count of contributions or a percentile of contributors of each crate
+    #[arg(long, default_value_t = ThresholdMode::Absolute)]
+    count_threshold_mode: ThresholdMode,
+
+    /// Scale the absolute threshold with crate size, to this fraction of all commits of a crate when that's more than the threshold
+    #[arg(long)]
+    adaptive_factor: Option<f64>,
+
+    /// Crates with fewer contributors than this are listed fully regardless of thresholds
+    #[arg(long, default_value_t = 2)]
+    small_crate_size: usize,
+
+    /// Skip crates with fewer contributors than this entirely, e.g. 2 to leave out single-maintainer crates
+    #[arg(long)]
+    min_crate_contributors: Option<usize>,
+
+    /// Prompt for the correct url of repositories that can't be parsed and whether to follow github forks upstream, answers are remembered in the cache, ignored unless stdin is a terminal
+    #[arg(long, default_value_t = false)]
+    interactive: bool,
+
+    /// Look up repositories of git dependencies on crates.io, using their git urls only for crates not published there
+    #[arg(long, default_value_t = false)]
+    prefer_registry_repo: bool,
+
     /// List other sources, not specified in Cargo.toml
     #[arg(short, long)]
     sources: Vec<String>,
 
+    /// Also acknowledge contributors of dependencies in this package.json, repositories are looked up on the npm registry, repeat for several
+    #[arg(long)]
+    include_npm: Vec<PathBuf>,
+
     /// Use your own template.
     /// See https://github.com/anvlkv/acknowledgements/blob/main/src/template.md?plain=1
     /// for reference
     #[arg(short, long)]
     template: Option<PathBuf>,
 
+    /// Register every .hbs and .md file in the directory as a partial named after its path,
+    /// template.hbs or template.md in it is then used as the template
+    #[arg(long)]
+    template_dir: Option<PathBuf>,
+
+    /// Whether to acknowledge contributors of a forked repository instead of following it to its upstream source
+    #[arg(long, default_value_t = false)]
+    include_forks: bool,
+
+    /// Show each contributor's share of all acknowledged contributions with NameAndCount format
+    #[arg(long, default_value_t = false)]
+    percentages: bool,
+
+    /// Refuse to fetch without a github access token when there are more github sources than this, 60 if no number is given
+    #[arg(long, num_args = 0..=1, default_missing_value = "60")]
+    require_token: Option<usize>,
+
+    /// Proxy url for all requests, HTTP_PROXY and HTTPS_PROXY env vars are used if not provided
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// Timeout in seconds for each request
+    #[arg(long)]
+    request_timeout: Option<u64>,
+
+    /// Number of gitlab and sourcehut repositories fetched at once, self-hosted instances may not tolerate many
+    #[arg(long, default_value_t = 1)]
+    other_concurrency: usize,
+
+    /// Overall time budget in seconds, after which fetching stops and output is generated from data fetched so far
+    #[arg(long)]
+    deadline: Option<u64>,
+
+    /// Overall seconds to wait for github rate limit resets, after which github fetching stops and output is generated from data fetched so far
+    #[arg(long, conflicts_with = "graphql")]
+    max_wait: Option<u64>,
+
+    /// Print seconds spent fetching crates.io data, each source and rendering, as Text or Json
+    #[arg(long, num_args = 0..=1, default_missing_value = "Text")]
+    timing: Option<Timing>,
+
+    /// Write timings to this file instead of stderr, as Json unless --timing says otherwise
+    #[arg(long)]
+    timing_file: Option<PathBuf>,
+
+    /// Order of the list
+    #[arg(long, default_value_t = Sort::Count)]
+    sort: Sort,
+
+    /// Order of crate sections with DepAndNames format, Name and Random sort take precedence
+    #[arg(long, default_value_t = CrateSort::Name)]
+    crate_sort: CrateSort,
+
+    /// Seed for the Random sort order
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Fetch fresh data instead of reading cache, cache is still updated with fetched data
+    #[arg(long, visible_alias = "refresh", default_value_t = false)]
+    no_cache: bool,
+
+    /// Max number of contributors listed per crate with DepAndNames format, the rest are counted as more
+    #[arg(long)]
+    max_contributors_per_crate: Option<usize>,
+
+    /// Max number of contributors listed with NameAndCount format, the rest are counted as more after sorting
+    #[arg(long)]
+    max_listed: Option<usize>,
+
+    /// Print contributors added or removed since the last run with --diff, compared to a .json snapshot stored next to the output file
+    #[arg(long, default_value_t = false)]
+    diff: bool,
+
+    /// Fail when a workspace member can't be loaded instead of skipping it
+    #[arg(long, default_value_t = false)]
+    strict: bool,
+
+    /// Analyze only dependencies declared in the manifest at path, without descending into workspace members
+    #[arg(long, default_value_t = false)]
+    no_workspace: bool,
+
+    /// Analyze only dependencies of the workspace member with this package name (like cargo's -p)
+    #[arg(long)]
+    package: Option<String>,
+
+    /// Fetch only the first page of github contributors, the most active ones, for a quicker approximate list
+    #[arg(long, default_value_t = false)]
+    shallow: bool,
+
+    /// Also acknowledge contributors of the Rust toolchain and standard library from rust-lang/rust, best combined with --shallow given its size
+    #[arg(long, default_value_t = false)]
+    include_rust: bool,
+
+    /// Write a table of dependencies with their licenses and repositories, as told by crates.io, instead of acknowledging contributors, to LICENSES.md unless output is given
+    #[arg(long, default_value_t = false)]
+    licenses: bool,
+
+    /// Prepend contents of this file to the output
+    #[arg(long)]
+    header: Option<PathBuf>,
+
+    /// Append contents of this file to the output
+    #[arg(long)]
+    footer: Option<PathBuf>,
+
+    /// Write a page per crate and an index.md linking them into this directory instead of a single file, with DepAndNames format
+    #[arg(long)]
+    split_output: Option<PathBuf>,
+
+    /// Give each crate a heading and list them in a table of contents, with DepAndNames format and the bundled template
+    #[arg(long, default_value_t = false)]
+    toc: bool,
+
+    /// Also count co-authors credited in commit trailers of github repos, scanning up to 1000 latest commits of each, which is slow and costs many requests
+    #[arg(long, default_value_t = false)]
+    co_authors: bool,
+
+    /// Show locked versions next to crate names with DepAndNames and NameAndDeps formats
+    #[arg(long, default_value_t = false)]
+    versions: bool,
+
+    /// Monthly commits of each contributor to github repos over the last months, 12 if no number is given, passed to templates as history with NameAndCount format, costs a commits listing of each repo
+    #[arg(long, num_args = 0..=1, default_missing_value = "12", conflicts_with = "graphql")]
+    history: Option<u32>,
+
+    /// Look up github organizations owning repositories and funding links in their .github/FUNDING.yml, passed to templates with DepAndNames format, costs a request per repo
+    #[arg(long, default_value_t = false, conflicts_with = "graphql")]
+    sponsors: bool,
+
+    /// Count github commits with the statistics api, precise for very active repos, falling back to contributors list when statistics aren't available
+    #[arg(long, default_value_t = false)]
+    stats: bool,
+
+    /// Fetch github sources in batches with GraphQL, counting authors of the latest 100 commits instead of all contributors, requires a token
+    #[arg(long, default_value_t = false)]
+    graphql: bool,
+
+    /// Acknowledge only these contributors, everyone else is ignored entirely
+    #[arg(long)]
+    only_contributor: Vec<String>,
+
+    /// Exclude authors of the analyzed project, as listed in its manifests, from the lists
+    #[arg(long, default_value_t = false)]
+    exclude_self: bool,
+
+    /// Exclude this login of yours, for when manifest authors don't match it
+    #[arg(long = "self", value_name = "LOGIN")]
+    self_logins: Vec<String>,
+
+    /// Fetch only from github.com, gitlab.com, hosts of your git dependencies and --sources, and these hosts, sources on other hosts are skipped with a warning, * allows any host
+    #[arg(long, value_delimiter = ',', value_name = "HOST")]
+    allowed_hosts: Vec<String>,
+
+    /// Merge gitlab contributors into the github users publishing their commit email on their profile, costs a github search for each email
+    #[arg(long, default_value_t = false)]
+    unify_by_email: bool,
+
+    /// Also write listed contributors to an all-contributors config, .all-contributorsrc of the project if no path is given, keeping its other settings
+    #[arg(long, num_args = 0..=1, value_name = "PATH")]
+    all_contributors: Option<Option<PathBuf>>,
+
+    /// Acknowledge bots in a separate section instead of dropping them
+    #[arg(long, default_value_t = false)]
+    group_bots_separately: bool,
+
+    /// Fetch only sources not processed by a previous run, reusing its results for the rest
+    #[arg(long, default_value_t = false)]
+    only_new: bool,
+
+    /// Min percentage of commits, relative to the top contributor of a crate, required to be listed
+    #[arg(long)]
+    min_share: Option<f64>,
+
+    /// Collapse contributors into others, least contributing first, as long as together they make up less than this percentage of all contributions
+    #[arg(long)]
+    others_tail_pct: Option<f64>,
+
+    /// Base directory for the cache, defaults to the platform's cache directory
+    #[arg(long, env = "ACKNOWLEDGE_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Command resolving contributors of sources on other hosts than github, gitlab and sourcehut, gets the repo url on stdin and prints json [{name, profile_url, commits}]
+    #[arg(long)]
+    source_resolver: Option<String>,
+
+    /// Self-hosted gitlab instances left to the gitlab api with --source-resolver, besides hosts named like gitlab
+    #[arg(long, value_delimiter = ',', value_name = "HOST")]
+    gitlab_hosts: Vec<String>,
+
+    /// Directory of local clones, laid out as owner/name of their repositories, tallied with git shortlog instead of fetching from forges
+    #[arg(long)]
+    local_repos: Option<PathBuf>,
+
+    /// Max age in hours of cached data, older entries are fetched again
+    #[arg(long)]
+    max_cache_age: Option<u64>,
+
+    /// Min number of crates.io downloads of a crate for its contributors to be acknowledged
+    #[arg(long)]
+    min_downloads: Option<u64>,
+
+    /// Order names ignoring case and accents rather than by code point, this isn't any locale's collation
+    #[arg(long)]
+    fold_accents: bool,
+
+    /// Include decorative emoji in the output, the default
+    #[arg(long, overrides_with = "no_emoji")]
+    emoji: bool,
+
+    /// Leave out decorative emoji, e.g. for docs linters rejecting them
+    #[arg(long, overrides_with = "emoji")]
+    no_emoji: bool,
+
+    /// Wording for contributors who didn't make it to the list
+    #[arg(long, default_value = "other cool humans")]
+    label_others: String,
+
+    /// Wording for a single contribution
+    #[arg(long, default_value = "contribution")]
+    label_contribution: String,
+
+    /// Wording for several contributions, when not just the plural of --label-contribution
+    #[arg(long)]
+    label_contributions: Option<String>,
+
+    #[command(flatten)]
+    features: FeatureFlags,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
+/// Cargo features to resolve optional dependencies with
+#[derive(clap::Args, Debug)]
+struct FeatureFlags {
+    /// Acknowledge optional dependencies enabled by these features, comma separated
+    #[arg(long, value_delimiter = ',')]
+    features: Vec<String>,
+
+    /// Acknowledge optional dependencies of all features
+    #[arg(long, default_value_t = false)]
+    all_features: bool,
+
+    /// Don't resolve optional dependencies enabled by the default feature
+    #[arg(long, default_value_t = false)]
+    no_default_features: bool,
+}
+
 #[derive(Subcommand, Debug)]
+#[allow(clippy::enum_variant_names)] // variants name the subcommands
 enum Commands {
     /// Clears cache
     ClearCache,
+    /// Exports cache entries to a portable json file, github access token is not exported
+    ExportCache { file: PathBuf },
+    /// Imports cache entries from a file created with export-cache
+    ImportCache { file: PathBuf },
+    /// Prints JSON Schema of the data passed to templates
+    Schema,
+    /// Generates the output and compares it to the existing file without writing, failing with a diff when it's stale
+    Check,
 }
 
 #[derive(Debug, Clone, Copy, strum_macros::Display, strum_macros::EnumString)]
@@ -86,6 +480,26 @@ enum Format {
     DepAndNames,
     /// Name of the contributor, names of dependencies where they contributed
     NameAndDeps,
+    /// Category of dependencies on crates.io, names of their contributors
+    CategoryAndNames,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, strum_macros::Display, strum_macros::EnumString)]
+#[strum(ascii_case_insensitive)]
+enum Markup {
+    /// Markdown, e.g. for READMEs and GitHub
+    Markdown,
+    /// reStructuredText, e.g. for Sphinx docs
+    Rst,
+}
+
+#[derive(Debug, Clone, Copy, strum_macros::Display, strum_macros::EnumString)]
+#[strum(ascii_case_insensitive)]
+enum Timing {
+    /// Lines of the summary
+    Text,
+    /// Array of phases and their seconds
+    Json,
 }
 
 #[derive(Debug, Clone, Copy, strum_macros::Display, strum_macros::EnumString)]
@@ -98,98 +512,459 @@ enum Breadth {
     BuildAndDev,
 }
 
-#[derive(Serialize, Deserialize)]
-struct GitLabContributor {
-    name: String,
-    commits: u32,
+#[derive(Debug, Clone, Copy, strum_macros::Display, strum_macros::EnumString)]
+#[strum(ascii_case_insensitive)]
+enum Sort {
+    /// Most contributions first, then by name
+    Count,
+    /// Alphabetically by name
+    Name,
+    /// Shuffled, to avoid implying a ranking
+    Random,
 }
 
-#[derive(Serialize, Deserialize)]
-struct GitLabProject {
-    name: String,
+#[derive(Debug, Clone, Copy, strum_macros::Display, strum_macros::EnumString)]
+#[strum(ascii_case_insensitive)]
+enum CrateSort {
+    /// Alphabetically by crate name
+    Name,
+    /// Most listed contributors first, then by name
+    Contributors,
+    /// Most downloaded on crates.io first, then by name
+    Downloads,
+}
+
+#[derive(Debug, Clone, Copy, strum_macros::Display, strum_macros::EnumString)]
+#[strum(ascii_case_insensitive)]
+enum ThresholdMode {
+    /// Min number of contributions
+    Absolute,
+    /// Min percentile of contributors of a crate, by their contributions
+    Percentile,
 }
 
+/// Repository urls and names of the crates they were resolved from
+type Sources = BTreeMap<String, BTreeSet<String>>;
+
+/// Crate name, its repository url, downloads, categories and license from crates.io
+type Resolved = (String, String, u64, Vec<String>, Option<String>);
+
+/// Repository name, login, profile url, avatar url and count of commits
+type Contribution = (String, String, String, String, u32);
+
 #[derive(Serialize, Deserialize)]
+struct CacheExport {
+    version: u8,
+    entries: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
 struct TemplateData {
     thank: Vec<ThankData>,
     others: usize,
+    others_contributions: usize,
+    more: usize,
+    more_contributions: usize,
     mention: bool,
+    bots: BTreeSet<(String, String)>,
+    toc: Vec<TocEntry>,
+    headings: bool,
+    emoji: bool,
+    labels: Labels,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Wording of the bundled template
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct Labels {
+    others: String,
+    contribution: String,
+    contributions: Option<String>,
+}
+
+/// Contributor in the all-contributors spec
+#[derive(Serialize)]
+struct AllContributor<'a> {
+    login: &'a str,
+    name: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    avatar_url: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    profile: &'a str,
+    contributions: [&'static str; 1],
+}
+
+/// Crate listed by DepAndNames, with its anchor for a table of contents
+#[derive(Serialize, Deserialize, Clone)]
+struct TocEntry {
+    name: String,
+    anchor: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 enum ThankData {
     NameAndCount {
         name: String,
         profile_url: String,
+        avatar_url: String,
         count: usize,
+        percent: Option<u32>,
+        history: Option<Vec<u32>>,
     },
     DepAndNames {
         crate_name: String,
         contributors: BTreeSet<(String, String)>,
+        more: usize,
+        organization: Option<String>,
+        sponsors: Vec<String>,
     },
     NameAndDeps {
         name: String,
         profile_url: String,
+        avatar_url: String,
+        crates: BTreeSet<String>,
+    },
+    CategoryAndNames {
+        category: String,
         crates: BTreeSet<String>,
+        contributors: BTreeSet<(String, String)>,
     },
 }
 
+impl ThankData {
+    fn name(&self) -> &str {
+        match self {
+            ThankData::NameAndCount { name, .. } => name,
+            ThankData::DepAndNames { crate_name, .. } => crate_name,
+            ThankData::NameAndDeps { name, .. } => name,
+            ThankData::CategoryAndNames { category, .. } => category,
+        }
+    }
+
+    fn contributors(&self) -> Vec<&str> {
+        match self {
+            ThankData::NameAndCount { name, .. } => vec![name],
+            ThankData::DepAndNames { contributors, .. }
+            | ThankData::CategoryAndNames { contributors, .. } => {
+                contributors.iter().map(|(name, _)| name.as_str()).collect()
+            }
+            ThankData::NameAndDeps { name, .. } => vec![name],
+        }
+    }
+}
+
+/// JSON Schema of [`TemplateData`], keep in sync with its fields
+fn template_schema() -> serde_json::Value {
+    let string = serde_json::json!({ "type": "string" });
+    let count = serde_json::json!({ "type": "integer", "minimum": 0 });
+    let pair = serde_json::json!({
+        "description": "Name and profile url, empty when unknown",
+        "type": "array",
+        "items": [string, string],
+        "minItems": 2,
+        "maxItems": 2
+    });
+    let variant = |name: &str, description: &str, properties: serde_json::Value| {
+        let required = Vec::from_iter(properties.as_object().into_iter().flat_map(|p| p.keys()));
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                name: {
+                    "description": description,
+                    "type": "object",
+                    "properties": properties,
+                    "required": required
+                }
+            },
+            "required": [name]
+        })
+    };
+
+    let thank = serde_json::json!({
+        "oneOf": [
+            variant(
+                "NameAndCount",
+                "Contributor and count of their contributions",
+                serde_json::json!({
+                    "name": string,
+                    "profile_url": string,
+                    "avatar_url": string,
+                    "count": count,
+                    "percent": {
+                        "description": "Share of all listed contributions, with --percentages",
+                        "type": ["integer", "null"]
+                    },
+                    "history": {
+                        "description": "Commits per month, oldest first, with --history",
+                        "type": ["array", "null"],
+                        "items": count
+                    }
+                }),
+            ),
+            variant(
+                "DepAndNames",
+                "Crate and its contributors",
+                serde_json::json!({
+                    "crate_name": string,
+                    "contributors": { "type": "array", "items": pair },
+                    "more": {
+                        "description": "Contributors left out by --max-contributors-per-crate",
+                        "type": "integer",
+                        "minimum": 0
+                    },
+                    "organization": {
+                        "description": "Github organization owning the repository, with --sponsors",
+                        "type": ["string", "null"]
+                    },
+                    "sponsors": {
+                        "description": "Funding links of the repository, with --sponsors",
+                        "type": "array",
+                        "items": string
+                    }
+                }),
+            ),
+            variant(
+                "NameAndDeps",
+                "Contributor and crates they contributed to",
+                serde_json::json!({
+                    "name": string,
+                    "profile_url": string,
+                    "avatar_url": string,
+                    "crates": { "type": "array", "items": string }
+                }),
+            ),
+            variant(
+                "CategoryAndNames",
+                "Crates.io category, its crates and their contributors",
+                serde_json::json!({
+                    "category": string,
+                    "crates": { "type": "array", "items": string },
+                    "contributors": { "type": "array", "items": pair }
+                }),
+            ),
+        ]
+    });
+
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "TemplateData",
+        "type": "object",
+        "properties": {
+            "thank": { "type": "array", "items": thank },
+            "others": {
+                "description": "Contributors who didn't make it to the list",
+                "type": "integer",
+                "minimum": 0
+            },
+            "others_contributions": count,
+            "more": {
+                "description": "Contributors left out by --max-listed with NameAndCount format",
+                "type": "integer",
+                "minimum": 0
+            },
+            "more_contributions": count,
+            "mention": { "type": "boolean" },
+            "bots": {
+                "description": "Bots, with --group-bots-separately",
+                "type": "array",
+                "items": pair
+            },
+            "toc": {
+                "description": "Crates listed by DepAndNames with anchors of their headings",
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": { "name": string, "anchor": string },
+                    "required": ["name", "anchor"]
+                }
+            },
+            "headings": {
+                "description": "Whether crates get headings and a table of contents, with --toc",
+                "type": "boolean"
+            },
+            "emoji": { "type": "boolean" },
+            "labels": {
+                "type": "object",
+                "properties": {
+                    "others": string,
+                    "contribution": string,
+                    "contributions": { "type": ["string", "null"] }
+                },
+                "required": ["others", "contribution", "contributions"]
+            }
+        },
+        "required": [
+            "thank",
+            "others",
+            "others_contributions",
+            "more",
+            "more_contributions",
+            "mention",
+            "bots",
+            "toc",
+            "headings",
+            "emoji",
+            "labels"
+        ]
+    })
+}
+
 #[tokio::main]
 async fn main() {
     match run().await {
-        Ok(_) => println!("Done!"),
-        Err(e) => eprintln!("Error: {e:?}"),
+        // like shells do for SIGINT, so that scripts tell an interrupted run from a complete one
+        Ok(_) if interrupt::interrupted() => std::process::exit(130),
+        Ok(_) => status!("Done!"),
+        Err(e) => {
+            _ = writeln!(anstream::stderr(), "{ERROR}Error:{ERROR:#} {e:?}");
+            std::process::exit(1);
+        }
     }
 }
 
 async fn run() -> anyhow::Result<()> {
     let args = Args::parse();
+    let started = Instant::now();
+    let deadline = args
+        .deadline
+        .map(|secs| Instant::now() + Duration::from_secs(secs));
+
+    let outputs = output_targets(args.format, &args.output);
+
+    if outputs
+        .iter()
+        .any(|(_, output)| output.as_deref() == Some(Path::new("-")))
+    {
+        STATUS_TO_STDERR.store(true, Ordering::Relaxed);
+    }
+
+    if let Some(dir) = args.cache_dir.clone() {
+        _ = CACHE_DIR.set(dir);
+    }
+
+    if args.path.iter().any(|path| is_stdin(path)) {
+        _ = STDIN_MANIFEST.set(std::io::read_to_string(std::io::stdin())?);
+    }
+
+    if let Some(hours) = args.max_cache_age {
+        _ = MAX_CACHE_AGE.set(Duration::from_secs(hours * 60 * 60));
+    }
 
-    if let Some(command) = args.command {
+    let check = matches!(args.command, Some(Commands::Check));
+
+    if let Some(command) = args.command.as_ref() {
         match command {
             Commands::ClearCache => return clear_cache().await,
+            Commands::ExportCache { file } => return export_cache(file).await,
+            Commands::ImportCache { file } => return import_cache(file).await,
+            Commands::Schema => {
+                // keeps the printed schema valid json
+                STATUS_TO_STDERR.store(true, Ordering::Relaxed);
+                println!("{}", serde_json::to_string_pretty(&template_schema())?);
+                return Ok(());
+            }
+            Commands::Check => {}
         }
     }
 
-    let mut github_sources: HashSet<String> = args
+    if check && (args.split_output.is_some() || STATUS_TO_STDERR.load(Ordering::Relaxed)) {
+        anyhow::bail!(
+            "check compares a single output file, it can't be combined with --split-output or -o -"
+        );
+    }
+
+    if args.split_output.is_some() && !matches!(outputs.as_slice(), [(Format::DepAndNames, _)]) {
+        anyhow::bail!("--split-output requires DepAndNames format and a single output");
+    }
+
+    let (mut github_sources, mut other_sources): (Sources, Sources) = args
         .sources
         .iter()
-        .filter_map(|s| {
-            if s.starts_with(GITHUB_AT_GIT) {
-                Some(s.replace(GITHUB_AT_GIT, GITHUB_BASE))
-            } else if s.starts_with(GITHUB_BASE) {
-                Some(s.clone())
-            } else {
-                None
+        .map(|s| (normalize_git_url(s), BTreeSet::new()))
+        .partition(|(s, _)| s.starts_with(GITHUB_BASE));
+
+    if args.include_rust {
+        if !args.shallow {
+            warn!(
+                "{RUST_REPOSITORY} has thousands of contributors, fetching them all costs many requests, --shallow lists only the most active ones"
+            );
+        }
+        _ = github_sources
+            .entry(RUST_REPOSITORY.to_string())
+            .or_default()
+            .insert("rust".to_string());
+    }
+
+    // a single member stands in for its workspace
+    let manifests = match args.package.as_deref() {
+        Some(package) => {
+            let members = args
+                .path
+                .iter()
+                .filter_map(|path| workspace_member(path, package))
+                .collect::<Vec<_>>();
+            if members.is_empty() {
+                anyhow::bail!("package {package} not found in the workspace");
             }
-        })
-        .collect();
+            members
+        }
+        None => args.path.clone(),
+    };
+    let with_members = !args.no_workspace && args.package.is_none();
 
-    let mut other_sources: HashSet<String> = args
-        .sources
-        .iter()
-        .filter(|s| !s.starts_with(GITHUB_AT_GIT) && !s.starts_with(GITHUB_BASE))
-        .cloned()
-        .collect();
+    let mut deps = vec![];
+    let mut skipped_members = vec![];
+    for path in manifests.iter() {
+        deps.extend(manifest_deps(
+            path,
+            &args.breadth,
+            with_members,
+            &args.features,
+            (!args.strict).then_some(&mut skipped_members),
+        )?);
+    }
+    dedup_deps(&mut deps);
 
-    let deps = manifest_deps(&args.path, &args.breadth)?;
+    status!("Analyzing {} dependencies...", deps.len());
+    let analyzed = deps.len();
 
-    println!("Analyzing {} dependencies...", deps.len());
+    let mut fetch_deps_data = BTreeSet::new();
+    let mut git_fallbacks = BTreeMap::new();
+    // refs github repositories of git dependencies are pinned to, by their urls
+    let mut pinned_refs: BTreeMap<String, String> = BTreeMap::new();
 
-    let mut fetch_deps_data = HashSet::new();
+    // hosts of repositories given in the project rather than taken from crate metadata
+    let mut allowed_hosts = args.allowed_hosts.clone();
+    allowed_hosts.extend(
+        github_sources
+            .keys()
+            .chain(other_sources.keys())
+            .filter_map(|src| url_host(src))
+            .map(String::from),
+    );
 
     for (name, dep) in deps {
         match dep {
             Dependency::Detailed(detail) => {
-                if let Some(git) = detail.git {
-                    if git.starts_with("https://github.com") || git.starts_with("git@github.com") {
-                        _ = github_sources
-                            .insert(git.replace("git@github.com", "https://github.com"));
+                if let Some(git) = detail.git.as_deref().map(normalize_git_url) {
+                    allowed_hosts.extend(url_host(&git).map(String::from));
+                    if let Some(git_ref) = pinned_ref(&git, &detail) {
+                        let pinned = pinned_refs.entry(git.clone()).or_insert(git_ref.clone());
+                        if *pinned != git_ref {
+                            warn!("{git} is pinned to both {pinned} and {git_ref}, its contributors are counted up to {pinned}");
+                        }
+                    }
+                    if args.prefer_registry_repo {
+                        let package = detail.package.unwrap_or(name);
+                        git_fallbacks.insert(package.clone(), git);
+                        fetch_deps_data.insert(package);
+                    } else if git.starts_with(GITHUB_BASE) {
+                        _ = github_sources.entry(git).or_default().insert(name);
                     } else {
-                        _ = other_sources.insert(git)
+                        _ = other_sources.entry(git).or_default().insert(name);
                     }
                 } else if detail.path.is_none() {
-                    fetch_deps_data.insert(name);
+                    // renamed dependencies are published under their package name
+                    fetch_deps_data.insert(detail.package.unwrap_or(name));
                 }
             }
             _ => {
@@ -198,29 +973,119 @@ async fn run() -> anyhow::Result<()> {
         }
     }
 
+    for replacement in args
+        .path
+        .iter()
+        .filter_map(|path| crates_io_replacement(path))
+    {
+        match replacement {
+        CratesIoReplacement::Vendored(dir) => {
+            // vendored manifests tell repositories without asking crates.io
+            let vendored = vendored_repositories(&dir);
+            fetch_deps_data.retain(|crate_name| match vendored.get(crate_name) {
+                Some(git) => {
+                    status!("vendored data for: {crate_name}");
+                    let git = normalize_git_url(git);
+                    if git.starts_with(GITHUB_BASE) {
+                        _ = github_sources.entry(git).or_default().insert(crate_name.clone());
+                    } else {
+                        _ = other_sources.entry(git).or_default().insert(crate_name.clone());
+                    }
+                    false
+                }
+                None => true,
+            });
+        }
+        CratesIoReplacement::Registry(name) => warn!(
+            "crates-io is replaced with {name} in cargo config, repositories are still looked up on crates.io and may be inaccurate"
+        ),
+    }
+    }
+
+    let no_cache = args.no_cache;
+
+    let http_client = client::http_client(
+        args.proxy.as_deref(),
+        args.request_timeout.map(Duration::from_secs),
+    )?;
+
     let (repo_sx, mut repo_rx) = unbounded_channel();
 
+    let crates_io_client = http_client.clone();
+    let crates_io_api = args
+        .crates_io_api
+        .clone()
+        .unwrap_or_else(|| CRATES_IO_API.to_string());
+
+    interrupt::install();
+
+    let crates_io_started = Instant::now();
+    let resolved_key = resolved_key(&fetch_deps_data, &locked_versions(&manifests));
+    let resolved_cached = read_cached_unless::<Vec<Resolved>>(no_cache, &resolved_key).await;
+    if resolved_cached.is_some() {
+        status!("{CACHED}cached crates.io data for unchanged dependencies{CACHED:#}");
+        CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+    }
+
     let out = tokio::spawn(async move {
-        let crates_io_client = crates_io_api::AsyncClient::new(
-            USER_AGENT,
-            std::time::Duration::from_millis(CRATES_IO_RATE_LIMIT),
-        )?;
+        if let Some(resolved) = resolved_cached {
+            for r in resolved {
+                repo_sx.send(r)?;
+            }
+            return anyhow::Ok(());
+        }
 
         for crate_name in fetch_deps_data {
+            if interrupt::interrupted() {
+                break;
+            }
+
             let c_key = format!("crates-io, {crate_name}");
 
-            if let Some(d) = read_cached(c_key.as_str()).await {
-                repo_sx.send(d)?;
-                println!("cached crates.io data for: {crate_name}");
+            if let Some((repo, downloads, categories, license)) =
+                read_cached_unless::<(String, u64, Vec<String>, Option<String>)>(
+                    no_cache,
+                    c_key.as_str(),
+                )
+                .await
+            {
+                repo_sx.send((crate_name.clone(), repo, downloads, categories, license))?;
+                status!("{CACHED}cached crates.io data for: {crate_name}{CACHED:#}");
+                CACHE_HITS.fetch_add(1, Ordering::Relaxed);
             } else {
                 let start = Instant::now();
-                println!("fetching crates.io data for: {crate_name}");
+                status!("{FETCHING}fetching crates.io data for: {crate_name}{FETCHING:#}");
+                FETCHES.fetch_add(1, Ordering::Relaxed);
 
-                let data = crates_io_client.get_crate(crate_name.as_str()).await?;
+                let data = crates_io_crate(&crates_io_client, &crates_io_api, &crate_name).await?;
+                if data.is_none() {
+                    let similar =
+                        crates_io_similar(&crates_io_client, &crates_io_api, &crate_name).await;
+                    if similar.is_empty() {
+                        warn!("{crate_name} not found on crates.io, skipping");
+                    } else {
+                        warn!(
+                            "{crate_name} not found on crates.io, skipping, similar crates: {}",
+                            similar.join(", ")
+                        );
+                    }
+                }
 
-                if let Some(r) = data.crate_data.repository {
-                    write_cached(c_key.as_str(), r.clone()).await;
-                    repo_sx.send(r)?;
+                if let Some(d) = data {
+                    if let Some(r) = d.crate_data.repository {
+                        let downloads = d.crate_data.downloads;
+                        let categories =
+                            Vec::from_iter(d.categories.into_iter().map(|c| c.category));
+                        // licenses are given per version, the latest one tells
+                        let max_version = d.crate_data.max_version;
+                        let license = d
+                            .versions
+                            .into_iter()
+                            .find(|v| v.num == max_version)
+                            .and_then(|v| v.license);
+                        write_cached(c_key.as_str(), (&r, downloads, &categories, &license)).await;
+                        repo_sx.send((crate_name.clone(), r, downloads, categories, license))?;
+                    }
                 }
 
                 if Instant::now().duration_since(start).as_millis() < CRATES_IO_RATE_LIMIT as u128 {
@@ -237,525 +1102,3124 @@ async fn run() -> anyhow::Result<()> {
         anyhow::Ok(())
     });
 
-    while let Some(git) = repo_rx.recv().await {
-        if git.starts_with(GITHUB_BASE) || git.starts_with(GITHUB_AT_GIT) {
-            _ = github_sources.insert(git.replace(GITHUB_AT_GIT, GITHUB_BASE).replace(".git", ""));
-        } else {
-            _ = other_sources.insert(git.replace(".git", ""));
+    let mut crate_categories = BTreeMap::new();
+    let mut crate_downloads = BTreeMap::new();
+    let mut crate_licenses = BTreeMap::new();
+    let mut resolved = vec![];
+
+    while let Some(r) = recv_until(&mut repo_rx, deadline).await {
+        resolved.push(r.clone());
+        let (crate_name, git, downloads, categories, license) = r;
+        git_fallbacks.remove(&crate_name);
+
+        if let Some(license) = license {
+            crate_licenses.insert(crate_name.clone(), license);
         }
-    }
 
-    _ = out.await??;
+        if args.min_downloads.is_some_and(|min| downloads < min) {
+            status!("skipping {crate_name}, downloaded only {downloads} times");
+            continue;
+        }
 
-    let (contrib_sx, mut contrib_rx) = unbounded_channel();
+        crate_downloads.insert(crate_name.clone(), downloads);
 
-    let gh_token = args
-        .gh_token
-        .or(read_cached::<Option<String>>("github_access_token")
-            .await
-            .flatten());
+        // subcategories are grouped with their parents
+        if let Some(category) = categories.first() {
+            let category = category.split("::").next().unwrap_or(category);
+            crate_categories.insert(crate_name.clone(), category.to_string());
+        }
 
-    if let Some(token) = gh_token.as_ref() {
-        write_cached("github_access_token", Some(token.clone())).await;
-    } else {
-        println!("Starting without github access token, may take longer...");
+        let git = normalize_git_url(&git);
+        if git.starts_with(GITHUB_BASE) {
+            _ = github_sources.entry(git).or_default().insert(crate_name);
+        } else {
+            _ = other_sources.entry(git).or_default().insert(crate_name);
+        }
     }
 
-    let out_gh = tokio::spawn({
-        let contrib_sx = contrib_sx.clone();
-        async move {
-            println!("{} github.com sources...", github_sources.len());
+    finish_fetching(out, deadline).await?;
+    record_timing("crates.io", crates_io_started);
 
-            let github_client = if let Some(tok) = gh_token {
-                std::sync::Arc::new(
-                    octocrab::OctocrabBuilder::new()
-                        .personal_token(tok)
-                        .build()?,
-                )
-            } else {
-                octocrab::instance()
-            };
+    if !(interrupt::interrupted() || deadline.is_some_and(|d| Instant::now() >= d)) {
+        write_cached(&resolved_key, &resolved).await;
+    }
 
-            for src in github_sources {
-                if let Some((data, contributors)) = read_cached::<(
-                    octocrab::models::Repository,
-                    Vec<octocrab::models::Contributor>,
-                )>(&src)
-                .await
-                {
-                    println!("cached github.com data for: {src}");
-
-                    for c in contributors {
-                        contrib_sx.send((
-                            data.name.clone(),
-                            c.author.login.clone(),
-                            c.author.html_url.to_string(),
-                            c.contributions,
-                        ))?;
-                    }
-                } else {
-                    let parsed = unformat!("https://github.com/{}/{}", &src);
-
-                    if let Some((owner, repo)) = parsed {
-                        // split-off any monorepo paths
-                        let repo = repo.split("/").next().unwrap_or(repo);
-
-                        println!("fetching github.com data for: {owner} {repo}");
-
-                        let mut contributors = vec![];
-                        let repo_handler = github_client.repos(owner, repo);
-                        let mut limit = gh_rate_limited(None, &github_client).await?;
-                        let data = repo_handler.get().await?;
-                        limit = gh_rate_limited(Some(limit), &github_client).await?;
-                        let first = repo_handler.list_contributors().send().await?;
-
-                        for c in first.items.iter() {
-                            contrib_sx.send((
-                                data.name.clone(),
-                                c.author.login.clone(),
-                                c.author.html_url.to_string(),
-                                c.contributions,
-                            ))?;
-                        }
+    // git dependencies unknown to crates.io
+    for (crate_name, git) in git_fallbacks {
+        if git.starts_with(GITHUB_BASE) {
+            _ = github_sources.entry(git).or_default().insert(crate_name);
+        } else {
+            _ = other_sources.entry(git).or_default().insert(crate_name);
+        }
+    }
 
-                        contributors.extend(first.items.clone());
-
-                        if let Some(pages) = first.number_of_pages() {
-                            for page in 2..=pages {
-                                limit = gh_rate_limited(Some(limit), &github_client).await?;
-                                let next =
-                                    repo_handler.list_contributors().page(page).send().await?;
-                                for c in next.items.iter() {
-                                    contrib_sx.send((
-                                        data.name.clone(),
-                                        c.author.login.clone(),
-                                        c.author.html_url.to_string(),
-                                        c.contributions,
-                                    ))?;
-                                }
-                                contributors.extend(next.items);
-                            }
-                        }
+    let mut packages = BTreeSet::new();
+    for path in args.include_npm.iter() {
+        packages.extend(package_json_deps(path, &args.breadth)?);
+    }
+    if !packages.is_empty() {
+        status!("Analyzing {} npm dependencies...", packages.len());
+    }
 
-                        write_cached(&src, (data, contributors)).await;
-                    } else {
-                        eprintln!("failed to parse github url: {src}");
+    for package in packages {
+        if interrupt::interrupted() {
+            break;
+        }
+
+        let key = format!("npm, {package}");
+        let repository = match read_cached_unless::<Option<String>>(no_cache, &key).await {
+            Some(repository) => {
+                status!("{CACHED}cached npm data for: {package}{CACHED:#}");
+                CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+                repository
+            }
+            None => {
+                status!("{FETCHING}fetching npm data for: {package}{FETCHING:#}");
+                FETCHES.fetch_add(1, Ordering::Relaxed);
+                // failures aren't cached, the next run tries again
+                let repository = match npm_repository(&http_client, &package).await {
+                    Ok(repository) => repository,
+                    Err(e) => {
+                        warn!("skipping npm package {package}: {e}");
+                        continue;
                     }
-                }
+                };
+                write_cached(&key, &repository).await;
+                repository
             }
+        };
 
-            anyhow::Ok(())
+        let Some(git) = repository else {
+            warn!("{package} has no repository on npm, skipping");
+            continue;
+        };
+
+        let git = normalize_git_url(&git);
+        if git.starts_with(GITHUB_BASE) {
+            _ = github_sources.entry(git).or_default().insert(package);
+        } else {
+            _ = other_sources.entry(git).or_default().insert(package);
         }
-    });
+    }
 
-    let out_gl = tokio::spawn(async move {
-        println!("{} other sources...", other_sources.len());
+    if interrupt::interrupted() {
+        status!("Interrupted, data fetched so far is cached, run again to resume");
+        return Ok(());
+    }
 
-        for src in other_sources {
-            if let Some((data, contributors)) =
-                read_cached::<(GitLabProject, Vec<GitLabContributor>)>(&src).await
-            {
-                println!("cached data for: {src}");
+    INTERACTIVE.store(
+        args.interactive && std::io::stdin().is_terminal(),
+        Ordering::Relaxed,
+    );
 
-                for c in contributors.iter() {
-                    contrib_sx.send((
-                        data.name.clone(),
-                        c.name.clone(),
-                        // TODO: get to user page url...
-                        Default::default(),
-                        c.commits,
-                    ))?;
-                }
-            } else {
-                let parsed = unformat!("https://{}/{}/{}", &src);
-                if let Some((base, owner, repo)) = parsed {
-                    // split-off any monorepo paths
-                    let repo = repo.split("/").next().unwrap_or(repo);
-
-                    let url = format!("https://{base}/api/v4/projects/{owner}%2F{repo}");
-                    println!("fetching {base} data for: {owner}/{repo}");
-                    let data = reqwest::get(&url).await?.json::<GitLabProject>().await?;
-                    let url = format!("{url}/repository/contributors");
-                    let contributors = reqwest::get(&url)
-                        .await?
-                        .json::<Vec<GitLabContributor>>()
-                        .await?;
-                    for c in contributors.iter() {
-                        contrib_sx.send((
-                            data.name.clone(),
-                            c.name.clone(),
-                            // TODO: get to user page url...
-                            Default::default(),
-                            c.commits,
-                        ))?;
-                    }
-                    write_cached(&src, (data, contributors)).await;
-                } else {
-                    eprintln!("failed to parse gitlab url: {src}");
-                }
+    // corrections given interactively are remembered for future runs
+    let all_sources = std::mem::take(&mut github_sources)
+        .into_iter()
+        .chain(std::mem::take(&mut other_sources));
+    for (src, crates) in all_sources {
+        let key = correction_key(&src);
+        let src = match read_cached::<String>(&key).await {
+            Some(corrected) => {
+                status!(
+                    "using corrected repository {corrected} instead of {src} ({})",
+                    from_crates(&crates)
+                );
+                corrected
             }
-        }
-
-        anyhow::Ok(())
-    });
+            None if is_ambiguous(&src) => match prompt(&format!(
+                "repository {src} ({}) can't be parsed, enter its url or leave empty to keep it:",
+                from_crates(&crates)
+            )) {
+                Some(corrected) if !corrected.is_empty() => {
+                    let corrected = normalize_git_url(&corrected);
+                    write_cached(&key, &corrected).await;
+                    corrected
+                }
+                _ => src,
+            },
+            None => src,
+        };
 
-    let mut contributions = BTreeMap::new();
+        if !is_allowed_host(&src, &allowed_hosts) {
+            warn!(
+                "skipping {src} ({}), its host isn't allowed, see --allowed-hosts",
+                from_crates(&crates)
+            );
+            continue;
+        }
 
-    while let Some((name, login, url, commits)) = contrib_rx.recv().await {
-        let e = contributions.entry(name).or_insert(vec![]);
-        if !login.ends_with("[bot]") {
-            e.push((login, url, commits));
+        if src.starts_with(GITHUB_BASE) {
+            github_sources.entry(src).or_default().extend(crates);
+        } else {
+            other_sources.entry(src).or_default().extend(crates);
         }
     }
 
-    _ = out_gh.await??;
-    _ = out_gl.await??;
-
-    println!("Got all data. generating...");
-
-    let mut handlebars = Handlebars::new();
-    handlebars.register_helper("plural", Box::new(plural_helper));
+    if args.licenses {
+        let table = licenses_table(
+            github_sources.iter().chain(other_sources.iter()),
+            &crate_licenses,
+        );
 
-    if let Some(p) = args.template {
-        let template = fs::read_to_string(p.as_path()).await?;
-        handlebars.register_template_string("template", template.as_str())?;
-    } else {
-        handlebars.register_template_string("template", TEMPLATE)?;
+        let output = outputs.first().and_then(|(_, output)| output.clone());
+        if output.as_deref() == Some(Path::new("-")) {
+            print!("{table}");
+        } else {
+            let path =
+                output.unwrap_or_else(|| project_dir(&args.path[0]).join(LICENSES_FILE_NAME));
+            if check {
+                let existing = fs::read_to_string(&path).await.unwrap_or_default();
+                check_up_to_date(&path, &existing, &table)?;
+            } else if !write_if_changed(&path, &table).await? {
+                status!("{} unchanged", path.display());
+            }
+        }
+        return Ok(());
     }
 
-    let threshold = args.contributions_threshold;
-    let data: TemplateData = match args.format {
-        Format::NameAndCount => {
-            let mut others = HashSet::new();
-            let mut thank = Vec::from_iter(
-                contributions
-                    .into_iter()
-                    .fold(HashMap::new(), |mut acc, (_, entries)| {
-                        let sole = entries.len() == 1;
-
-                        for (login, profile_url, commits) in entries {
-                            if !sole && (commits as usize) < threshold {
-                                _ = others.insert(login);
-                                continue;
-                            } else {
-                                _ = others.remove(&login);
-                            }
+    let (contrib_sx, mut contrib_rx) = unbounded_channel();
 
-                            let entry =
-                                acc.entry(login.clone()).or_insert(ThankData::NameAndCount {
-                                    name: login,
-                                    profile_url,
-                                    count: 0,
-                                });
-                            match entry {
-                                ThankData::NameAndCount { count, .. } => *count += commits as usize,
-                                _ => unreachable!(),
-                            }
-                        }
-                        acc
-                    })
-                    .into_values(),
-            );
+    let crate_sources: BTreeMap<String, String> = github_sources
+        .iter()
+        .chain(other_sources.iter())
+        .flat_map(|(src, crates)| crates.iter().map(|c| (c.clone(), src.clone())))
+        .collect();
 
-            thank.sort_by(|th_1, th_2| match (th_1, th_2) {
-                (
-                    ThankData::NameAndCount {
-                        count: count_1,
-                        name: name_1,
-                        ..
-                    },
-                    ThankData::NameAndCount {
-                        count: count_2,
-                        name: name_2,
-                        ..
-                    },
-                ) => {
-                    let o = count_2.cmp(count_1);
-                    match o {
-                        std::cmp::Ordering::Equal => name_1.cmp(name_2),
-                        std::cmp::Ordering::Less => o,
-                        std::cmp::Ordering::Greater => o,
-                    }
+    let processed_key = processed_key(&args);
+    if args.only_new {
+        if let Some(previous) =
+            read_cached::<BTreeMap<String, Vec<Contribution>>>(&processed_key).await
+        {
+            let mut reused = 0;
+            for (src, entries) in previous {
+                // pins may have moved since, their counts are cached along with the ref anyway
+                if pinned_refs.contains_key(&src) {
+                    continue;
+                }
+                if github_sources.remove(&src).is_none() && other_sources.remove(&src).is_none() {
+                    continue;
+                }
+                reused += 1;
+                for contribution in entries {
+                    contrib_sx.send((src.clone(), contribution))?;
                 }
-                _ => unreachable!(),
-            });
-
-            TemplateData {
-                thank,
-                others: others.len(),
-                mention: args.mention,
             }
+            status!("{reused} sources processed by a previous run, fetching only new ones...");
         }
-        Format::DepAndNames => {
-            let mut others = HashSet::new();
+    }
 
-            let thank = contributions
-                .into_iter()
-                .map(|(crate_name, contributors)| ThankData::DepAndNames {
-                    crate_name,
-                    contributors: {
-                        let sole = contributors.len() == 1;
+    // other users can read the temp dir, the token isn't kept there
+    let keeps_token = !temp_cache();
+    let gh_token = match args.gh_token {
+        Some(token) => Some(token),
+        None if keeps_token => read_cached::<Option<String>>(TOKEN_CACHE_KEY)
+            .await
+            .flatten(),
+        None => None,
+    };
 
-                        BTreeSet::from_iter(contributors.into_iter().filter_map(
-                            |(login, url, commits)| {
-                                if !sole && (commits as usize) < threshold {
-                                    _ = others.insert(login);
-                                    None
-                                } else {
-                                    _ = others.remove(&login);
-                                    Some((login, url))
-                                }
-                            },
-                        ))
-                    },
-                })
-                .collect();
-            TemplateData {
-                thank,
-                others: others.len(),
-                mention: args.mention,
-            }
+    if let Some(token) = gh_token.as_ref() {
+        if keeps_token {
+            write_cached(TOKEN_CACHE_KEY, Some(token.clone())).await;
+        } else {
+            warn!("the github access token isn't cached in the shared temp dir, pass it again next time or choose --cache-dir");
         }
-        Format::NameAndDeps => {
-            let mut others = HashSet::new();
-
-            let mut thank = Vec::from_iter(
-                contributions
-                    .into_iter()
-                    .fold(HashMap::new(), |mut acc, (crate_name, entries)| {
-                        let sole = entries.len() == 1;
-
-                        for (login, profile_url, commits) in entries {
-                            if !sole && (commits as usize) < threshold {
-                                _ = others.insert(login);
-                                continue;
-                            } else {
-                                _ = others.remove(&login);
-                            }
+    } else {
+        status!("Starting without github access token, may take longer...");
+    }
 
-                            let entry =
-                                acc.entry(login.clone()).or_insert(ThankData::NameAndDeps {
-                                    name: login,
-                                    profile_url,
-                                    crates: BTreeSet::new(),
-                                });
-                            match entry {
-                                ThankData::NameAndDeps { crates, .. } => {
-                                    _ = crates.insert(crate_name.clone());
-                                }
-                                _ => unreachable!(),
-                            }
-                        }
-                        acc
-                    })
-                    .into_values(),
-            );
-            thank.sort_by(|th_1, th_2| match (th_1, th_2) {
-                (
-                    ThankData::NameAndDeps {
-                        crates: crates_1,
-                        name: name_1,
-                        ..
-                    },
-                    ThankData::NameAndDeps {
-                        crates: crates_2,
-                        name: name_2,
-                        ..
-                    },
-                ) => {
-                    let o = crates_2.len().cmp(&crates_1.len());
-                    match o {
-                        std::cmp::Ordering::Equal => name_1.cmp(name_2),
-                        std::cmp::Ordering::Less => o,
-                        std::cmp::Ordering::Greater => o,
-                    }
+    // local clones take over from forges
+    let local_repos = args.local_repos.map(LocalRepos::new);
+    let mut local_sources = Sources::new();
+    if let Some(local_repos) = local_repos.as_ref() {
+        for sources in [&mut github_sources, &mut other_sources] {
+            sources.retain(|src, crates| {
+                if local_repos.has_clone(src) {
+                    local_sources.insert(src.clone(), std::mem::take(crates));
+                    false
+                } else {
+                    true
                 }
-                _ => unreachable!(),
             });
-            TemplateData {
-                thank,
-                others: others.len(),
-                mention: args.mention,
-            }
         }
+    }
+    let local_count = local_sources.len();
+
+    let github_count = github_sources.len();
+
+    if let Some(max) = args
+        .require_token
+        .filter(|max| gh_token.is_none() && github_count > *max)
+    {
+        anyhow::bail!(
+            "{github_count} github sources exceed {max} allowed without a token, rate limits may stall the run for hours, please provide one with --gh-token"
+        );
+    }
+
+    if args.graphql && gh_token.is_none() {
+        anyhow::bail!("--graphql requires a github access token");
+    }
+
+    let github_client = client::github_client(
+        http_client.clone(),
+        gh_token.clone(),
+        args.github_api.as_deref(),
+    )?;
+    if let Some(token) = gh_token.as_deref().filter(|_| github_count > 0) {
+        client::check_github_token(&github_client, token).await?;
+    }
+    let users = GitHubUsers::new(github_client.clone());
+    let sources_started = Instant::now();
+    let out_gh = if args.graphql {
+        tokio::spawn(fetch_contributors(
+            GitHubGraphQl::new(github_client, args.include_forks),
+            github_sources,
+            contrib_sx.clone(),
+            no_cache,
+        ))
+    } else {
+        tokio::spawn(fetch_contributors(
+            GitHub::new(
+                github_client,
+                args.include_forks,
+                args.shallow,
+                args.stats,
+                args.co_authors,
+                args.history,
+                args.sponsors,
+                no_cache,
+                args.max_wait,
+                pinned_refs,
+            ),
+            github_sources,
+            contrib_sx.clone(),
+            no_cache,
+        ))
     };
 
-    // println!("data: {}", serde_json::to_string(&data)?);
+    let (sourcehut_sources, other_sources): (Sources, Sources) = other_sources
+        .into_iter()
+        .partition(|(src, _)| SOURCEHUT_BASES.iter().any(|base| src.starts_with(base)));
+
+    // with a resolver only gitlab hosts are left to the gitlab api
+    let is_gitlab = |host: &str| {
+        host.contains("gitlab")
+            || args
+                .gitlab_hosts
+                .iter()
+                .any(|h| h.eq_ignore_ascii_case(host))
+    };
+    let (resolver_sources, other_sources): (Sources, Sources) =
+        other_sources.into_iter().partition(|(src, _)| {
+            args.source_resolver.is_some() && !src.split('/').nth(2).is_some_and(is_gitlab)
+        });
+    if !resolver_sources.is_empty() {
+        let hosts = BTreeSet::from_iter(
+            resolver_sources
+                .keys()
+                .filter_map(|src| src.split('/').nth(2)),
+        );
+        status!(
+            "resolving sources on {} with the source resolver, list self-hosted gitlab instances among them with --gitlab-hosts",
+            Vec::from_iter(hosts).join(", ")
+        );
+    }
 
-    let generated = handlebars.render("template", &data)?;
+    let sourcehut_count = sourcehut_sources.len();
+    let other_count = other_sources.len();
+    let resolver_count = resolver_sources.len();
 
-    let output_file_path = args.output.unwrap_or_else(|| {
-        let mut path = args.path.clone();
-        path.push(FILE_NAME);
-        path
+    let out_resolver = args.source_resolver.map(|command| {
+        tokio::spawn(fetch_contributors(
+            Resolver::new(command),
+            resolver_sources,
+            contrib_sx.clone(),
+            no_cache,
+        ))
     });
 
-    fs::write(output_file_path, generated).await?;
+    // clones change with every pull, they're read afresh each run
+    let out_local = local_repos.map(|local_repos| {
+        tokio::spawn(fetch_contributors(
+            local_repos,
+            local_sources,
+            contrib_sx.clone(),
+            true,
+        ))
+    });
 
-    Ok(())
-}
+    let out_srht = tokio::spawn(fetch_contributors(
+        SourceHut::new(http_client.clone(), args.other_concurrency.max(1)),
+        sourcehut_sources,
+        contrib_sx.clone(),
+        no_cache,
+    ));
 
-async fn gh_rate_limited(
-    limit: Option<RateLimit>,
-    client: &octocrab::Octocrab,
-) -> anyhow::Result<RateLimit> {
-    let mut limit = match limit {
-        Some(l) => l,
-        None => client.ratelimit().get().await?,
-    };
+    let out_gl = tokio::spawn(fetch_contributors(
+        GitLab::new(
+            http_client,
+            args.gitlab_api.clone(),
+            args.other_concurrency.max(1),
+            args.unify_by_email,
+        ),
+        other_sources,
+        contrib_sx,
+        no_cache,
+    ));
 
-    if limit.resources.core.remaining > 0 {
-        limit.resources.core.remaining -= 1;
-        anyhow::Ok(limit)
-    } else {
-        let timeout =
-            chrono::DateTime::<chrono::Utc>::from_timestamp(limit.resources.core.reset as i64, 0)
-                .expect("create timeout");
-        let now = chrono::Utc::now();
-        let duration = timeout.signed_duration_since(now);
-        let seconds = duration.num_seconds() as u64;
-        for _ in 1..=seconds {
-            let now = chrono::Utc::now();
-            let duration = timeout.signed_duration_since(now);
-            print!("\rHonouring your contributors {} requests were made, now please honour github's rate limit, and wait kindly {:0>2}m {:0>2}s...",
-                limit.resources.core.limit,
-                duration.num_minutes(),
-                duration.num_seconds() - duration.num_minutes() * 60,
-            );
-            std::io::Write::flush(&mut std::io::stdout()).unwrap();
+    let mut contributions = BTreeMap::new();
+    let mut bots = BTreeSet::new();
+    let mut processed: BTreeMap<String, Vec<Contribution>> = BTreeMap::new();
+
+    while let Some((src, contribution)) = recv_until(&mut contrib_rx, deadline).await {
+        processed.entry(src).or_default().push(contribution.clone());
 
-            sleep(Duration::from_secs(1)).await;
+        let (name, login, url, avatar_url, commits) = contribution;
+
+        let e = contributions.entry(name).or_insert(vec![]);
+        if !login.ends_with("[bot]") {
+            e.push((login, url, avatar_url, commits));
+        } else if args.group_bots_separately {
+            bots.insert((login, url));
         }
-        let mut new_limit = client.ratelimit().get().await?;
-        new_limit.resources.core.limit += limit.resources.core.limit;
-        anyhow::Ok(new_limit)
     }
-}
 
-fn plural_helper(
-    h: &handlebars::Helper,
-    _: &Handlebars,
-    _: &handlebars::Context,
-    _: &mut handlebars::RenderContext,
-    out: &mut dyn handlebars::Output,
-) -> handlebars::HelperResult {
-    let count = h
-        .param(0)
-        .map(|p| p.value().as_number().map(|p| p.as_u64()))
-        .flatten()
-        .flatten()
-        .ok_or(handlebars::RenderErrorReason::MissingVariable(Some(
-            "expected count param".to_string(),
-        )))?;
+    if args.unify_by_email {
+        unify_by_email(&users, &processed, &mut contributions, no_cache).await;
+    }
 
-    let singular = h.param(1).map(|p| p.value().as_str()).flatten().ok_or(
-        handlebars::RenderErrorReason::MissingVariable(Some("expected singular param".to_string())),
-    )?;
+    let mut curated: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
 
-    let plural = h.param(2).map(|p| p.value().as_str()).flatten().ok_or(
-        handlebars::RenderErrorReason::MissingVariable(Some("expected plural param".to_string())),
-    )?;
+    let credits = args.path.iter().fold(BTreeMap::new(), |mut acc, path| {
+        // the first project locking a crate has its say
+        for (crate_name, credits) in metadata_credits(path, crate_sources.keys()) {
+            acc.entry(crate_name).or_insert(credits);
+        }
+        acc
+    });
 
-    if count == 1 {
-        out.write(singular)?;
-    } else {
-        out.write(plural)?;
+    for (crate_name, credits) in credits {
+        // contributions are listed by repository name
+        let name = crate_sources
+            .get(&crate_name)
+            .and_then(|src| processed.get(src))
+            .and_then(|entries| entries.first())
+            .map(|(name, ..)| name.clone())
+            .unwrap_or(crate_name.clone());
+
+        status!(
+            "crediting {} contributors from package metadata of: {crate_name}",
+            credits.len()
+        );
+
+        let entries = contributions.entry(name.clone()).or_insert(vec![]);
+        for credit in credits {
+            if !entries.iter().any(|(login, ..)| login == &credit.name) {
+                entries.push((
+                    credit.name.clone(),
+                    credit.url,
+                    Default::default(),
+                    credit.contributions.unwrap_or(1),
+                ));
+            }
+            curated.entry(name.clone()).or_default().insert(credit.name);
+        }
     }
 
-    Ok(())
-}
+    if let Some(min) = args.min_crate_contributors {
+        contributions.retain(|name, entries| {
+            let community = entries.len() >= min;
+            if !community {
+                status!("skipping {name}, only {} contributors", entries.len());
+            }
+            community
+        });
+    }
 
-async fn read_cached<T>(key: &str) -> Option<T>
-where
-    T: serde::de::DeserializeOwned,
-{
-    if let Some(dir) = dirs::cache_dir() {
-        let mut path = dir.clone();
-        path.push(CACHE_NAME);
-        cacache::read(path, key)
-            .await
-            .map(|d: Vec<u8>| serde_json::from_slice::<T>(d.as_slice()).ok())
-            .ok()
-            .flatten()
+    // arrival order varies between runs
+    for entries in contributions.values_mut() {
+        entries.sort();
+    }
+
+    if !args.only_contributor.is_empty() {
+        for entries in contributions.values_mut() {
+            entries.retain(|(login, ..)| args.only_contributor.contains(login));
+        }
+        contributions.retain(|_, entries| !entries.is_empty());
+    }
+
+    let mut excluded = BTreeSet::from_iter(args.self_logins.iter().map(|l| l.to_lowercase()));
+    if args.exclude_self {
+        for path in manifests.iter() {
+            excluded.extend(
+                manifest_authors(path, with_members)?
+                    .iter()
+                    .map(|a| a.to_lowercase()),
+            );
+        }
+    }
+    if !excluded.is_empty() {
+        for entries in contributions.values_mut() {
+            entries.retain(|(login, ..)| !excluded.contains(&login.to_lowercase()));
+        }
+        contributions.retain(|_, entries| !entries.is_empty());
+    }
+
+    finish_fetching(out_gh, deadline).await?;
+    finish_fetching(out_gl, deadline).await?;
+    finish_fetching(out_srht, deadline).await?;
+    if let Some(out_resolver) = out_resolver {
+        finish_fetching(out_resolver, deadline).await?;
+    }
+    if let Some(out_local) = out_local {
+        finish_fetching(out_local, deadline).await?;
+    }
+    record_timing("sources", sources_started);
+
+    if interrupt::interrupted() {
+        status!("Interrupted, data fetched so far is cached, run again to resume");
+        return Ok(());
+    } else if deadline.is_some_and(|d| Instant::now() >= d) {
+        status!("Deadline reached, generating from data fetched so far...");
+    } else if WAIT_EXCEEDED.load(Ordering::Relaxed) > 0 {
+        warn!(
+            "{} github sources weren't fetched within --max-wait, output is incomplete, run again to resume",
+            WAIT_EXCEEDED.load(Ordering::Relaxed)
+        );
     } else {
-        None
+        write_cached(&processed_key, &processed).await;
     }
-}
 
-async fn write_cached<T>(key: &str, data: T)
-where
-    T: serde::ser::Serialize,
-{
-    if let Some(dir) = dirs::cache_dir() {
-        let mut path = dir.clone();
-        path.push(CACHE_NAME);
+    status!("Got all data. generating...");
+
+    let unique_contributors = contributions
+        .values()
+        .flatten()
+        .map(|(login, ..)| login)
+        .collect::<HashSet<_>>()
+        .len();
 
-        if let Ok(data) = serde_json::to_vec(&data) {
-            _ = cacache::write(path, key, data).await;
+    let rendering_started = Instant::now();
+    let mut handlebars = Handlebars::new();
+    handlebars.register_helper("plural", Box::new(plural_helper));
+    handlebars.register_helper("slug", Box::new(slug_helper));
+    handlebars.register_helper("md", Box::new(markdown_helper));
+    handlebars.register_helper("rst", Box::new(rst_helper));
+    handlebars.register_helper("rst_heading", Box::new(rst_heading_helper));
+
+    if let Some(dir) = args.template_dir.as_ref() {
+        for tpl_extension in [".md", ".hbs"] {
+            handlebars.register_templates_directory(
+                dir,
+                DirectorySourceOptions {
+                    tpl_extension: tpl_extension.to_string(),
+                    ..Default::default()
+                },
+            )?;
         }
     }
-}
 
-async fn clear_cache() -> anyhow::Result<()> {
-    if let Some(dir) = dirs::cache_dir() {
-        let mut path = dir.clone();
-        path.push(CACHE_NAME);
-        cacache::clear(path).await?;
+    if let Some(p) = args.template {
+        let template = fs::read_to_string(p.as_path()).await?;
+        handlebars.register_template_string("template", template.as_str())?;
+    } else if !handlebars.has_template("template") {
+        handlebars.register_template_string("template", TEMPLATE)?;
+        handlebars.register_template_string("template.rst", TEMPLATE_RST)?;
     }
-    anyhow::Ok(())
-}
 
-fn manifest_deps(path: &PathBuf, depth: &Breadth) -> anyhow::Result<Vec<(String, Dependency)>> {
-    let manifest = Manifest::from_path(path.as_path()).or_else(|_| {
-        let mut path = path.clone();
-        path.push("Cargo.toml");
-        Manifest::from_path(path.as_path())
-    })?;
+    let threshold = args.contributions_threshold;
+    let min_share = args.min_share;
+    // the long tail of contributors, by their totals across all crates
+    let mut tail = HashSet::new();
+    if let Some(pct) = args.others_tail_pct {
+        let mut totals: HashMap<&str, u64> = HashMap::new();
+        for (login, .., commits) in contributions.values().flatten() {
+            *totals.entry(login.as_str()).or_insert(0) += *commits as u64;
+        }
+        let total: u64 = totals.values().sum();
+        let mut ascending = Vec::from_iter(totals);
+        ascending.sort_by(|(login_1, total_1), (login_2, total_2)| {
+            total_1.cmp(total_2).then(login_1.cmp(login_2))
+        });
 
-    let mut deps: Vec<_> = match depth {
-        Breadth::NonOpt => manifest
-            .dependencies
-            .iter()
-            .filter(|d| !d.1.optional())
-            .map(|(k, d)| (k.clone(), d.clone()))
-            .collect(),
-        Breadth::All => manifest
-            .dependencies
-            .iter()
-            .map(|(k, d)| (k.clone(), d.clone()))
-            .collect(),
-        Breadth::BuildAndDev => manifest
-            .dependencies
+        let mut collapsed = 0;
+        for (login, commits) in ascending {
+            if ((collapsed + commits) as f64) >= total as f64 * pct / 100.0 {
+                break;
+            }
+            collapsed += commits;
+            tail.insert(login.to_string());
+        }
+    }
+    let small_crate_size = args.small_crate_size;
+    let mode = args.count_threshold_mode;
+    // ascending commits of each crate's contributors, for percentiles
+    let counts: BTreeMap<String, Vec<u32>> =
+        BTreeMap::from_iter(contributions.iter().map(|(crate_name, entries)| {
+            let mut counts = Vec::from_iter(entries.iter().map(|(.., commits)| *commits));
+            counts.sort();
+            (crate_name.clone(), counts)
+        }));
+    let adaptive_factor = args.adaptive_factor;
+    let totals: BTreeMap<String, u64> = BTreeMap::from_iter(
+        counts
             .iter()
-            .chain(manifest.dev_dependencies.iter())
-            .chain(manifest.build_dependencies.iter())
-            .map(|(k, d)| (k.clone(), d.clone()))
-            .collect(),
+            .map(|(crate_name, c)| (crate_name.clone(), c.iter().map(|c| *c as u64).sum())),
+    );
+    let short_of_threshold = move |crate_name: &str, commits: u32| match mode {
+        ThresholdMode::Absolute => {
+            // large crates have a long tail, small ones keep the flat threshold
+            let effective = adaptive_factor.map_or(threshold as f64, |factor| {
+                let total = totals.get(crate_name).copied().unwrap_or(0);
+                (threshold as f64).max(total as f64 * factor)
+            });
+            (commits as f64) < effective
+        }
+        ThresholdMode::Percentile => {
+            // percentile rank is the share of contributors with fewer commits
+            let counts = counts
+                .get(crate_name)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let fewer = counts.partition_point(|c| *c < commits);
+            fewer * 100 < threshold * counts.len()
+        }
+    };
+    // whether commits fall short of the threshold or of the share of a crate's top contributor,
+    // contributors credited in package metadata never do
+    let below = move |crate_name: &str, login: &str, commits: u32, top: u32| {
+        !curated.get(crate_name).is_some_and(|c| c.contains(login))
+            && (tail.contains(login)
+                || short_of_threshold(crate_name, commits)
+                || min_share.is_some_and(|pct| (commits as f64) < top as f64 * pct / 100.0))
+    };
+    let fold_accents = args.fold_accents;
+    let cmp_names = |name_1: &str, name_2: &str| {
+        if fold_accents {
+            collation_key(name_1)
+                .cmp(&collation_key(name_2))
+                .then(name_1.cmp(name_2))
+        } else {
+            name_1.cmp(name_2)
+        }
     };
 
-    if let Some(workspace) = manifest.workspace {
-        match depth {
-            Breadth::BuildAndDev => deps.extend(
-                workspace
-                    .dependencies
+    // crates with contributors of which none made it, e.g. with a too high threshold
+    let unlisted = Vec::from_iter(
+        contributions
+            .iter()
+            .filter(|(_, entries)| !entries.is_empty() && entries.len() >= small_crate_size)
+            .filter(|(crate_name, entries)| {
+                let top = entries
                     .iter()
-                    .map(|(k, d)| (k.clone(), d.clone())),
-            ),
-            _ => deps.extend(
-                workspace
-                    .dependencies
+                    .map(|(.., commits)| *commits)
+                    .max()
+                    .unwrap_or(0);
+                entries
                     .iter()
-                    .filter(|d| !d.1.optional())
-                    .map(|(k, d)| (k.clone(), d.clone())),
-            ),
-        }
+                    .all(|(login, .., commits)| below(crate_name, login, *commits, top))
+            })
+            .map(|(crate_name, _)| crate_name.as_str()),
+    );
+    if !unlisted.is_empty() {
+        warn!(
+            "all contributors of {} fall below the thresholds and are counted as others, consider lowering them",
+            unlisted.join(", ")
+        );
+    }
 
-        for member in workspace.members.iter() {
-            let mut member_path = path.clone();
-            member_path.push(member);
-            deps.extend(manifest_deps(&member_path, depth)?);
+    // contributors listed with no crate are others, decided once for all formats
+    let mut others: BTreeMap<&str, usize> = BTreeMap::new();
+    // listed contributors with their profile and avatar urls and listed contributions
+    let mut listed: BTreeMap<&str, (&str, &str, usize)> = BTreeMap::new();
+    for (crate_name, entries) in contributions.iter() {
+        let small = entries.len() < small_crate_size;
+        let top = entries
+            .iter()
+            .map(|(.., commits)| *commits)
+            .max()
+            .unwrap_or(0);
+        for (login, url, avatar_url, commits) in entries {
+            if !small && below(crate_name, login, *commits, top) {
+                *others.entry(login).or_insert(0) += *commits as usize;
+            } else {
+                let entry = listed.entry(login).or_insert((url, avatar_url, 0));
+                entry.2 += *commits as usize;
+            }
         }
     }
+    others.retain(|login, _| !listed.contains_key(login));
+    let others_count = others.len();
+    let others_contributions: usize = others.values().sum();
 
-    Ok(deps)
+    let mut summary_others = None;
+
+    for (format, output) in outputs.iter() {
+        // each format consumes its own copy of the data
+        let contributions = contributions.clone();
+        let format = *format;
+
+        let mut data: TemplateData = match format {
+            Format::NameAndCount => {
+                let mut thank = Vec::from_iter(
+                    contributions
+                        .into_iter()
+                        .fold(HashMap::new(), |mut acc, (crate_name, entries)| {
+                            let small = entries.len() < small_crate_size;
+                            let top = entries
+                                .iter()
+                                .map(|(.., commits)| *commits)
+                                .max()
+                                .unwrap_or(0);
+
+                            for (login, profile_url, avatar_url, commits) in entries {
+                                if !small && below(&crate_name, &login, commits, top) {
+                                    continue;
+                                }
+
+                                let entry =
+                                    acc.entry(login.clone()).or_insert(ThankData::NameAndCount {
+                                        name: login,
+                                        profile_url,
+                                        avatar_url,
+                                        count: 0,
+                                        percent: None,
+                                        history: None,
+                                    });
+                                match entry {
+                                    ThankData::NameAndCount { count, .. } => {
+                                        *count += commits as usize
+                                    }
+                                    _ => unreachable!(),
+                                }
+                            }
+                            acc
+                        })
+                        .into_values(),
+                );
+
+                thank.sort_by(|th_1, th_2| match (th_1, th_2) {
+                    (
+                        ThankData::NameAndCount {
+                            count: count_1,
+                            name: name_1,
+                            ..
+                        },
+                        ThankData::NameAndCount {
+                            count: count_2,
+                            name: name_2,
+                            ..
+                        },
+                    ) => {
+                        let o = count_2.cmp(count_1);
+                        match o {
+                            std::cmp::Ordering::Equal => cmp_names(name_1, name_2),
+                            std::cmp::Ordering::Less => o,
+                            std::cmp::Ordering::Greater => o,
+                        }
+                    }
+                    _ => unreachable!(),
+                });
+
+                if args.percentages {
+                    let total: usize = thank
+                        .iter()
+                        .map(|th| match th {
+                            ThankData::NameAndCount { count, .. } => *count,
+                            _ => unreachable!(),
+                        })
+                        .sum();
+                    for th in thank.iter_mut() {
+                        if let ThankData::NameAndCount { count, percent, .. } = th {
+                            *percent = Some((*count as f64 * 100.0 / total as f64).round() as u32);
+                        }
+                    }
+                }
+
+                if let Some(months) = args.history {
+                    let mut histories: HashMap<String, Vec<u32>> = HashMap::new();
+                    for src in processed.keys().filter(|src| src.starts_with(GITHUB_BASE)) {
+                        let key = sources::history_key(src, months);
+                        for (login, buckets) in read_cached::<BTreeMap<String, Vec<u32>>>(&key)
+                            .await
+                            .unwrap_or_default()
+                        {
+                            let sum = histories
+                                .entry(login)
+                                .or_insert_with(|| vec![0; months as usize]);
+                            for (total, commits) in sum.iter_mut().zip(buckets) {
+                                *total += commits;
+                            }
+                        }
+                    }
+                    for th in thank.iter_mut() {
+                        if let ThankData::NameAndCount { name, history, .. } = th {
+                            *history = Some(
+                                histories
+                                    .remove(name.as_str())
+                                    .unwrap_or_else(|| vec![0; months as usize]),
+                            );
+                        }
+                    }
+                }
+
+                TemplateData {
+                    thank,
+                    others: others_count,
+                    others_contributions,
+                    mention: args.mention,
+                    ..Default::default()
+                }
+            }
+            Format::DepAndNames => {
+                let max_per_crate = args.max_contributors_per_crate;
+
+                let mut thank: Vec<ThankData> = contributions
+                    .into_iter()
+                    .map(|(crate_name, contributors)| {
+                        let small = contributors.len() < small_crate_size;
+                        let top = contributors
+                            .iter()
+                            .map(|(.., commits)| *commits)
+                            .max()
+                            .unwrap_or(0);
+
+                        let mut listed = Vec::from_iter(contributors.into_iter().filter_map(
+                            |(login, url, _, commits)| {
+                                (small || !below(&crate_name, &login, commits, top))
+                                    .then_some((login, url, commits))
+                            },
+                        ));
+
+                        let mut more = 0;
+
+                        if let Some(max) = max_per_crate {
+                            listed.sort_by(
+                                |(login_1, url_1, commits_1), (login_2, url_2, commits_2)| {
+                                    commits_2
+                                        .cmp(commits_1)
+                                        .then(login_1.cmp(login_2))
+                                        .then(url_1.cmp(url_2))
+                                },
+                            );
+                            more = listed.len().saturating_sub(max);
+                            listed.truncate(max);
+                        }
+
+                        ThankData::DepAndNames {
+                            crate_name,
+                            contributors: BTreeSet::from_iter(
+                                listed.into_iter().map(|(login, url, _)| (login, url)),
+                            ),
+                            more,
+                            organization: None,
+                            sponsors: vec![],
+                        }
+                    })
+                    .collect();
+
+                match args.crate_sort {
+                    CrateSort::Name => {}
+                    CrateSort::Contributors => thank.sort_by_cached_key(|th| match th {
+                        ThankData::DepAndNames {
+                            contributors, more, ..
+                        } => std::cmp::Reverse(contributors.len() + more),
+                        _ => unreachable!(),
+                    }),
+                    CrateSort::Downloads => {
+                        // crates sharing a repository count with the downloads of the one named after it
+                        let downloads =
+                            by_repository_name(&crate_sources, &processed, &crate_downloads);
+                        thank.sort_by_cached_key(|th| {
+                            std::cmp::Reverse(downloads.get(th.name()).copied().unwrap_or(0))
+                        });
+                    }
+                }
+
+                TemplateData {
+                    thank,
+                    others: others_count,
+                    others_contributions,
+                    mention: args.mention,
+                    ..Default::default()
+                }
+            }
+            Format::NameAndDeps => {
+                let mut thank = Vec::from_iter(
+                    contributions
+                        .into_iter()
+                        .fold(HashMap::new(), |mut acc, (crate_name, entries)| {
+                            let small = entries.len() < small_crate_size;
+                            let top = entries
+                                .iter()
+                                .map(|(.., commits)| *commits)
+                                .max()
+                                .unwrap_or(0);
+
+                            for (login, profile_url, avatar_url, commits) in entries {
+                                if !small && below(&crate_name, &login, commits, top) {
+                                    continue;
+                                }
+
+                                let entry =
+                                    acc.entry(login.clone()).or_insert(ThankData::NameAndDeps {
+                                        name: login,
+                                        profile_url,
+                                        avatar_url,
+                                        crates: BTreeSet::new(),
+                                    });
+                                match entry {
+                                    ThankData::NameAndDeps { crates, .. } => {
+                                        _ = crates.insert(crate_name.clone());
+                                    }
+                                    _ => unreachable!(),
+                                }
+                            }
+                            acc
+                        })
+                        .into_values(),
+                );
+                thank.sort_by(|th_1, th_2| match (th_1, th_2) {
+                    (
+                        ThankData::NameAndDeps {
+                            crates: crates_1,
+                            name: name_1,
+                            ..
+                        },
+                        ThankData::NameAndDeps {
+                            crates: crates_2,
+                            name: name_2,
+                            ..
+                        },
+                    ) => {
+                        let o = crates_2.len().cmp(&crates_1.len());
+                        match o {
+                            std::cmp::Ordering::Equal => cmp_names(name_1, name_2),
+                            std::cmp::Ordering::Less => o,
+                            std::cmp::Ordering::Greater => o,
+                        }
+                    }
+                    _ => unreachable!(),
+                });
+                TemplateData {
+                    thank,
+                    others: others_count,
+                    others_contributions,
+                    mention: args.mention,
+                    ..Default::default()
+                }
+            }
+            Format::CategoryAndNames => {
+                let categories = by_repository_name(&crate_sources, &processed, &crate_categories);
+                let mut groups = BTreeMap::new();
+
+                for (crate_name, contributors) in contributions {
+                    let small = contributors.len() < small_crate_size;
+                    let top = contributors
+                        .iter()
+                        .map(|(.., commits)| *commits)
+                        .max()
+                        .unwrap_or(0);
+                    let category = categories
+                        .get(&crate_name)
+                        .map(String::as_str)
+                        .unwrap_or(UNCATEGORIZED);
+                    let group =
+                        groups
+                            .entry(category.to_string())
+                            .or_insert(ThankData::CategoryAndNames {
+                                category: category.to_string(),
+                                crates: BTreeSet::new(),
+                                contributors: BTreeSet::new(),
+                            });
+                    let ThankData::CategoryAndNames {
+                        crates,
+                        contributors: listed,
+                        ..
+                    } = group
+                    else {
+                        unreachable!()
+                    };
+
+                    for (login, url, _, commits) in contributors {
+                        if small || !below(&crate_name, &login, commits, top) {
+                            crates.insert(crate_name.clone());
+                            listed.insert((login, url));
+                        }
+                    }
+                }
+
+                let thank = groups
+                    .into_values()
+                    .filter(|group| !group.contributors().is_empty())
+                    .collect();
+                TemplateData {
+                    thank,
+                    others: others_count,
+                    others_contributions,
+                    mention: args.mention,
+                    ..Default::default()
+                }
+            }
+        };
+
+        data.bots = bots.clone();
+
+        if args.sponsors {
+            let mut funding = BTreeMap::new();
+            for (crate_name, src) in crate_sources.iter() {
+                if let Some(owner) =
+                    read_cached::<(Option<String>, Vec<String>)>(&sources::sponsors_key(src)).await
+                {
+                    funding.insert(crate_name.clone(), owner);
+                }
+            }
+            let funding = by_repository_name(&crate_sources, &processed, &funding);
+
+            for th in data.thank.iter_mut() {
+                if let ThankData::DepAndNames {
+                    crate_name,
+                    organization,
+                    sponsors,
+                    ..
+                } = th
+                {
+                    if let Some((org, links)) = funding.get(crate_name.as_str()) {
+                        organization.clone_from(org);
+                        sponsors.clone_from(links);
+                    }
+                }
+            }
+        }
+
+        if args.versions {
+            let locked = locked_versions(&args.path);
+            let versions = by_repository_name(&crate_sources, &processed, &locked);
+
+            let versioned = |name: &mut String| {
+                if let Some(version) = versions.get(name.as_str()) {
+                    *name = format!("{name} {version}");
+                }
+            };
+
+            for th in data.thank.iter_mut() {
+                match th {
+                    ThankData::DepAndNames { crate_name, .. } => versioned(crate_name),
+                    ThankData::NameAndDeps { crates, .. }
+                    | ThankData::CategoryAndNames { crates, .. } => {
+                        *crates = std::mem::take(crates)
+                            .into_iter()
+                            .map(|mut name| {
+                                versioned(&mut name);
+                                name
+                            })
+                            .collect();
+                    }
+                    ThankData::NameAndCount { .. } => {}
+                }
+            }
+        }
+
+        data.emoji = !args.no_emoji;
+        data.headings = args.toc;
+        data.labels = Labels {
+            others: args.label_others.clone(),
+            contribution: args.label_contribution.clone(),
+            contributions: args.label_contributions.clone(),
+        };
+
+        match args.sort {
+            Sort::Count => {}
+            Sort::Name => data
+                .thank
+                .sort_by(|th_1, th_2| cmp_names(th_1.name(), th_2.name())),
+            Sort::Random => {
+                let mut rng = match args.seed {
+                    Some(seed) => fastrand::Rng::with_seed(seed),
+                    None => fastrand::Rng::new(),
+                };
+                rng.shuffle(&mut data.thank);
+            }
+        }
+
+        // the cap applies to the list as sorted
+        if let Some(max) = args.max_listed {
+            if matches!(format, Format::NameAndCount) && data.thank.len() > max {
+                let rest = data.thank.split_off(max);
+                data.more = rest.len();
+                data.more_contributions = rest
+                    .iter()
+                    .map(|th| match th {
+                        ThankData::NameAndCount { count, .. } => *count,
+                        _ => unreachable!(),
+                    })
+                    .sum();
+            }
+        }
+
+        let mut anchors = HashMap::new();
+        data.toc = data
+            .thank
+            .iter()
+            .filter_map(|th| match th {
+                ThankData::DepAndNames { crate_name, .. } => Some(crate_name),
+                _ => None,
+            })
+            .map(|name| {
+                // repeated headings get numbered anchors
+                let slug = slugify(name);
+                let seen = anchors.entry(slug.clone()).or_insert(0);
+                let anchor = if *seen == 0 {
+                    slug
+                } else {
+                    format!("{slug}-{seen}")
+                };
+                *seen += 1;
+                TocEntry {
+                    name: name.clone(),
+                    anchor,
+                }
+            })
+            .collect();
+
+        // println!("data: {}", serde_json::to_string(&data)?);
+
+        // custom templates are used whatever the markup
+        let rst = args.markup == Markup::Rst
+            || output
+                .as_deref()
+                .is_some_and(|o| o.extension().is_some_and(|ext| ext == "rst"));
+        let template = if rst && handlebars.has_template("template.rst") {
+            "template.rst"
+        } else {
+            "template"
+        };
+
+        let mut generated = handlebars.render(template, &data)?;
+
+        if let Some(header) = args.header.as_ref() {
+            generated.insert_str(0, &fs::read_to_string(header).await?);
+        }
+        if let Some(footer) = args.footer.as_ref() {
+            generated.push_str(&fs::read_to_string(footer).await?);
+        }
+
+        let to_stdout = output.as_deref() == Some(Path::new("-"));
+        let output_file_path = output.clone().filter(|_| !to_stdout).unwrap_or_else(|| {
+            // several projects are acknowledged in the first one
+            let mut path = project_dir(&args.path[0]).join(FILE_NAME);
+            if rst {
+                path.set_extension("rst");
+            }
+            path
+        });
+
+        if args.diff && !check {
+            let snapshot_path = output_file_path.with_extension("json");
+            let listed = BTreeSet::from_iter(
+                data.thank
+                    .iter()
+                    .flat_map(|th| th.contributors())
+                    .map(String::from),
+            );
+
+            match fs::read(&snapshot_path).await {
+                Ok(previous) => {
+                    let previous: BTreeSet<String> = serde_json::from_slice(previous.as_slice())?;
+                    print_diff(&previous, &listed);
+                }
+                Err(_) => status!("No previous snapshot, storing one for the next run..."),
+            }
+
+            fs::write(snapshot_path, serde_json::to_vec_pretty(&listed)?).await?;
+        }
+
+        if let Some(dir) = args.split_output.as_ref() {
+            fs::create_dir_all(&dir).await?;
+
+            let (ext, mut index) = if rst {
+                (
+                    "rst",
+                    String::from("Acknowledgements\n================\n\n"),
+                )
+            } else {
+                ("md", String::from("# Acknowledgements\n\n"))
+            };
+
+            for (th, entry) in data.thank.iter().zip(data.toc.iter()) {
+                // each page is rendered from its crate only
+                let page = TemplateData {
+                    thank: vec![th.clone()],
+                    mention: data.mention,
+                    toc: vec![entry.clone()],
+                    emoji: data.emoji,
+                    labels: data.labels.clone(),
+                    ..Default::default()
+                };
+                let file_name = format!("{}.{ext}", entry.anchor);
+                write_if_changed(&dir.join(&file_name), &handlebars.render(template, &page)?)
+                    .await?;
+                if rst {
+                    index.push_str(&format!("- `{} <{file_name}>`__\n", entry.name));
+                } else {
+                    index.push_str(&format!("- [{}]({file_name})\n", entry.name));
+                }
+            }
+
+            status!("Wrote {} crate pages to {}", data.toc.len(), dir.display());
+            write_if_changed(&dir.join(format!("index.{ext}")), &index).await?;
+        } else if check {
+            let existing = fs::read_to_string(&output_file_path)
+                .await
+                .unwrap_or_default();
+            let expected = between_markers(&existing, &generated).unwrap_or(generated);
+            check_up_to_date(&output_file_path, &existing, &expected)?;
+        } else if to_stdout {
+            print!("{generated}");
+        } else {
+            // hand-written parts around the markers survive regeneration
+            if let Some(marked) = fs::read_to_string(&output_file_path)
+                .await
+                .ok()
+                .and_then(|existing| between_markers(&existing, &generated))
+            {
+                generated = marked;
+            }
+            if !write_if_changed(&output_file_path, &generated).await? {
+                status!("{} unchanged", output_file_path.display());
+            }
+        }
+
+        summary_others.get_or_insert(data.others);
+    }
+    record_timing("rendering", rendering_started);
+
+    if let Some(path) = args.all_contributors.as_ref().filter(|_| !check) {
+        let path = path
+            .clone()
+            .unwrap_or_else(|| project_dir(&args.path[0]).join(ALL_CONTRIBUTORS_FILE_NAME));
+        let existing = fs::read(&path).await.ok();
+        let rc = all_contributors_rc(existing.as_deref(), &listed)?;
+        if !write_if_changed(&path, &rc).await? {
+            status!("{} unchanged", path.display());
+        }
+    }
+
+    // scripted runs keep stderr to warnings and errors
+    if std::io::stderr().is_terminal() {
+        eprintln!("Summary:");
+        eprintln!("  crates analyzed: {analyzed}");
+        if !skipped_members.is_empty() {
+            eprintln!(
+                "  skipped workspace members: {}",
+                Vec::from_iter(skipped_members.iter().map(|m| m.display().to_string())).join(", ")
+            );
+        }
+        let mut sources_summary =
+            format!("{github_count} github.com, {sourcehut_count} sourcehut, {other_count} gitlab");
+        if resolver_count > 0 {
+            sources_summary.push_str(&format!(", {resolver_count} resolver"));
+        }
+        if local_count > 0 {
+            sources_summary.push_str(&format!(", {local_count} local"));
+        }
+        eprintln!("  sources: {sources_summary}");
+        eprintln!(
+            "  contributors: {unique_contributors}, {} of them in others",
+            summary_others.unwrap_or_default()
+        );
+        if let Some(empty) = EMPTY_SOURCES.lock().ok().filter(|empty| !empty.is_empty()) {
+            eprintln!(
+                "  sources without contributors: {}",
+                Vec::from_iter(empty.iter().map(String::as_str)).join(", ")
+            );
+        }
+        eprintln!(
+            "  cache hits: {}, fetches: {}",
+            CACHE_HITS.load(Ordering::Relaxed),
+            FETCHES.load(Ordering::Relaxed)
+        );
+        eprintln!("  elapsed: {:.1}s", started.elapsed().as_secs_f64());
+    }
+
+    let timing = args
+        .timing
+        .or(args.timing_file.as_ref().map(|_| Timing::Json));
+    if let Some(timing) = timing {
+        let timings = TIMINGS.lock().map(|t| t.clone()).unwrap_or_default();
+        let report = match timing {
+            Timing::Text => {
+                let mut report = String::from("Timing:\n");
+                for (phase, secs) in timings {
+                    report.push_str(&format!("  {phase}: {secs:.1}s\n"));
+                }
+                report
+            }
+            Timing::Json => {
+                let timings = Vec::from_iter(timings.into_iter().map(
+                    |(phase, seconds)| serde_json::json!({ "phase": phase, "seconds": seconds }),
+                ));
+                format!("{}\n", serde_json::to_string_pretty(&timings)?)
+            }
+        };
+
+        // stderr carries the summary and warnings too, a file keeps json parseable
+        match args.timing_file.as_ref() {
+            Some(path) => fs::write(path, report).await?,
+            None => eprint!("{report}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Notes seconds spent in a phase or fetching a source since it started, for --timing
+fn record_timing(phase: &str, started: Instant) {
+    if let Ok(mut timings) = TIMINGS.lock() {
+        timings.push((phase.to_string(), started.elapsed().as_secs_f64()));
+    }
+}
+
+/// Values of crates keyed by names of their repositories, which contributions are listed by,
+/// crates sharing a repository have the value of the crate named like it, or else the first one
+fn by_repository_name<T: Clone>(
+    crate_sources: &BTreeMap<String, String>,
+    processed: &BTreeMap<String, Vec<Contribution>>,
+    values: &BTreeMap<String, T>,
+) -> BTreeMap<String, T> {
+    let mut by_name = BTreeMap::new();
+
+    for (crate_name, src) in crate_sources.iter() {
+        let (Some(value), Some((name, ..))) = (
+            values.get(crate_name),
+            processed.get(src).and_then(|entries| entries.first()),
+        ) else {
+            continue;
+        };
+        if crate_name == name || !by_name.contains_key(name) {
+            by_name.insert(name.clone(), value.clone());
+        }
+    }
+
+    by_name
+}
+
+/// Formats and the files they're written to, given as `Format=path` outputs or as
+/// paths in the format of `--format`, a single one to the default file if none is given
+fn output_targets(format: Format, outputs: &[PathBuf]) -> Vec<(Format, Option<PathBuf>)> {
+    if outputs.is_empty() {
+        return vec![(format, None)];
+    }
+
+    outputs
+        .iter()
+        .map(|output| {
+            let pair = output.to_str().and_then(|o| {
+                let (name, path) = o.split_once('=')?;
+                Some((name.parse::<Format>().ok()?, PathBuf::from(path)))
+            });
+            match pair {
+                Some((format, path)) => (format, Some(path)),
+                None => (format, Some(output.clone())),
+            }
+        })
+        .collect()
+}
+
+/// Markdown table of crates with their licenses and repositories, ordered by crate name
+fn licenses_table<'a>(
+    sources: impl Iterator<Item = (&'a String, &'a BTreeSet<String>)>,
+    licenses: &BTreeMap<String, String>,
+) -> String {
+    let rows = BTreeMap::from_iter(
+        sources.flat_map(|(src, crates)| crates.iter().map(move |crate_name| (crate_name, src))),
+    );
+
+    let mut table = String::from("| Crate | License | Repository |\n| --- | --- | --- |\n");
+    for (crate_name, src) in rows {
+        let license = licenses.get(crate_name).map_or("unknown", String::as_str);
+        table.push_str(&format!("| {crate_name} | {license} | {src} |\n"));
+    }
+    table
+}
+
+/// Writes the file unless it has these contents already, so that mtimes don't churn,
+/// whether it was written
+async fn write_if_changed(path: &Path, contents: &str) -> anyhow::Result<bool> {
+    if fs::read(path)
+        .await
+        .is_ok_and(|existing| existing == contents.as_bytes())
+    {
+        return Ok(false);
+    }
+    fs::write(path, contents).await?;
+    Ok(true)
+}
+
+/// All-contributors config crediting listed github users for code, merged into an existing
+/// config by login, so that its settings and people added by hand with other contributions
+/// are kept, newcomers are appended by their contributions, most first
+fn all_contributors_rc(
+    existing: Option<&[u8]>,
+    listed: &BTreeMap<&str, (&str, &str, usize)>,
+) -> anyhow::Result<String> {
+    let mut rc = match existing {
+        Some(existing) => serde_json::from_slice(existing)?,
+        None => serde_json::Map::new(),
+    };
+    let mut contributors = match rc.remove("contributors") {
+        Some(serde_json::Value::Array(contributors)) => contributors,
+        _ => vec![],
+    };
+
+    // names of gitlab and sourcehut contributors aren't logins all-contributors could link
+    let mut newcomers = Vec::from_iter(
+        listed
+            .iter()
+            .filter(|(_, (profile, ..))| profile.starts_with(GITHUB_BASE)),
+    );
+    newcomers.sort_by(|(login_1, (.., count_1)), (login_2, (.., count_2))| {
+        count_2.cmp(count_1).then(login_1.cmp(login_2))
+    });
+
+    for (login, (profile, avatar_url, _)) in newcomers {
+        let known = contributors.iter_mut().find(|c| {
+            c.get("login")
+                .and_then(|l| l.as_str())
+                .is_some_and(|l| l.eq_ignore_ascii_case(login))
+        });
+
+        match known {
+            Some(serde_json::Value::Object(known)) => {
+                // commits are all we know of
+                let contributions = known
+                    .entry("contributions")
+                    .or_insert_with(|| serde_json::json!([]));
+                if let Some(contributions) = contributions.as_array_mut() {
+                    if !contributions.iter().any(|c| c == "code") {
+                        contributions.push("code".into());
+                    }
+                }
+                for (key, value) in [("avatar_url", avatar_url), ("profile", profile)] {
+                    if !value.is_empty() {
+                        known.entry(key).or_insert_with(|| (*value).into());
+                    }
+                }
+            }
+            _ => contributors.push(serde_json::to_value(AllContributor {
+                login,
+                name: login,
+                avatar_url,
+                profile,
+                contributions: ["code"],
+            })?),
+        }
+    }
+
+    rc.insert(
+        "contributors".to_string(),
+        serde_json::Value::Array(contributors),
+    );
+
+    Ok(serde_json::to_string_pretty(&rc)? + "\n")
+}
+
+/// Existing file with the generated list in place of whatever was between its markers
+fn between_markers(existing: &str, generated: &str) -> Option<String> {
+    let (before, rest) = existing.split_once(BEGIN_MARKER)?;
+    let (_, after) = rest.split_once(END_MARKER)?;
+
+    Some(format!(
+        "{before}{BEGIN_MARKER}\n{generated}{END_MARKER}{after}"
+    ))
+}
+
+fn print_diff(previous: &BTreeSet<String>, listed: &BTreeSet<String>) {
+    let added = Vec::from_iter(listed.difference(previous).map(String::as_str));
+    let removed = Vec::from_iter(previous.difference(listed).map(String::as_str));
+
+    if added.is_empty() && removed.is_empty() {
+        status!("No changes in acknowledged contributors since last run");
+    }
+    if !added.is_empty() {
+        status!("Newly acknowledged: {}", added.join(", "));
+    }
+    if !removed.is_empty() {
+        status!("No longer acknowledged: {}", removed.join(", "));
+    }
+}
+
+/// Host of a repository url, without its port
+fn url_host(src: &str) -> Option<&str> {
+    let host = src.split('/').nth(2)?;
+    host.split(':').next()
+}
+
+/// Whether a repository url is on github.com, gitlab.com or one of the allowed hosts,
+/// any host is allowed with *
+fn is_allowed_host(src: &str, allowed: &[String]) -> bool {
+    if allowed.iter().any(|host| host == "*") {
+        return true;
+    }
+    let Some(host) = url_host(src) else {
+        return false;
+    };
+
+    ALLOWED_HOSTS
+        .iter()
+        .copied()
+        .chain(allowed.iter().map(String::as_str))
+        .any(|allowed| allowed.eq_ignore_ascii_case(host))
+}
+
+/// Whether a repository url lacks a host or a path to the repository on it
+fn is_ambiguous(src: &str) -> bool {
+    match src.strip_prefix("https://") {
+        Some(rest) => rest.split('/').filter(|s| !s.is_empty()).count() < 3,
+        None => true,
+    }
+}
+
+fn correction_key(src: &str) -> String {
+    format!("{src}, corrected")
+}
+
+/// Trimmed answer to a question asked on stderr, none unless interactive
+fn prompt(question: &str) -> Option<String> {
+    if !INTERACTIVE.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    eprint!("{question} ");
+    _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).ok()?;
+    Some(answer.trim().to_string())
+}
+
+/// Names of the crates a source was resolved from, for logs
+fn from_crates(crates: &BTreeSet<String>) -> String {
+    if crates.is_empty() {
+        "from --sources".to_string()
+    } else {
+        Vec::from_iter(crates.iter().map(String::as_str)).join(", ")
+    }
+}
+
+/// Fetches contributors of every repository from source, or reads them from cache
+async fn fetch_contributors(
+    source: impl ContributorSource,
+    repos: Sources,
+    contrib_sx: UnboundedSender<(String, Contribution)>,
+    no_cache: bool,
+) -> anyhow::Result<()> {
+    status!("{} {} sources...", repos.len(), source.name());
+    let started = Instant::now();
+
+    let mut pending = vec![];
+
+    for (src, crates) in repos {
+        let key = source.cache_key(&src);
+
+        if let Some(cached) = read_cached_unless::<Vec<Contribution>>(no_cache, &key).await {
+            status!("{CACHED}cached {} data for: {src}{CACHED:#}", source.name());
+            CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+            if cached.is_empty() {
+                no_contributors(&src, &crates);
+            }
+
+            for contribution in cached {
+                contrib_sx.send((src.clone(), contribution))?;
+            }
+        } else if let Some(e) = read_failure_unless(no_cache, &key).await {
+            status!(
+                "{CACHED}skipping previously failed {src} ({}): {e}{CACHED:#}",
+                from_crates(&crates)
+            );
+            CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+        } else {
+            pending.push((src, crates));
+        }
+    }
+
+    let mut exceeded = false;
+
+    for batch in pending.chunks(source.batch_size()) {
+        if interrupt::interrupted() {
+            break;
+        }
+
+        // the rest won't get quota either
+        if exceeded {
+            WAIT_EXCEEDED.fetch_add(batch.len(), Ordering::Relaxed);
+            continue;
+        }
+
+        for (src, crates) in batch {
+            status!(
+                "{FETCHING}fetching {} data for: {src} ({}){FETCHING:#}",
+                source.name(),
+                from_crates(crates)
+            );
+            FETCHES.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let repos = Vec::from_iter(batch.iter().map(|(src, _)| src.clone()));
+        // fetches in flight are abandoned, completed ones are cached already
+        let fetched = tokio::select! {
+            fetched = source.fetch_many(&repos) => fetched,
+            _ = interrupt::wait() => break,
+        };
+
+        for ((src, crates), fetched) in batch.iter().zip(fetched) {
+            match fetched {
+                Ok(contributions) => {
+                    let key = source.cache_key(src);
+                    write_cached(&key, &contributions).await;
+                    remove_cached(&failure_key(&key)).await;
+                    if contributions.is_empty() {
+                        no_contributors(src, crates);
+                    }
+
+                    for contribution in contributions {
+                        contrib_sx.send((src.clone(), contribution))?;
+                    }
+                }
+                Err(e) if e.is::<WaitExceeded>() => {
+                    exceeded = true;
+                    WAIT_EXCEEDED.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    warn!("skipping {src} ({}): {e}", from_crates(crates));
+
+                    // transient failures are retried by the next run
+                    if sources::is_permanent(&e) {
+                        write_cached(&failure_key(&source.cache_key(src)), e.to_string()).await;
+                    }
+                }
+            }
+        }
+    }
+
+    record_timing(source.name(), started);
+
+    anyhow::Ok(())
+}
+
+/// Replaces gitlab contributors with the github users publishing their commit email,
+/// so that people contributing on both forges are listed once
+async fn unify_by_email(
+    users_by_email: &GitHubUsers,
+    processed: &BTreeMap<String, Vec<Contribution>>,
+    contributions: &mut BTreeMap<String, Vec<(String, String, String, u32)>>,
+    no_cache: bool,
+) {
+    let mut users = HashMap::new();
+
+    for (src, entries) in processed {
+        let Some(emails) = read_cached::<BTreeMap<String, String>>(&sources::emails_key(src)).await
+        else {
+            continue;
+        };
+
+        for (name, login, ..) in entries {
+            let Some(email) = emails.get(login) else {
+                continue;
+            };
+
+            if !users.contains_key(email) {
+                let key = format!("github user, {email}");
+                let user =
+                    match read_cached_unless::<Option<(String, String, String)>>(no_cache, &key)
+                        .await
+                    {
+                        Some(user) => {
+                            CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+                            user
+                        }
+                        None => {
+                            status!("{FETCHING}looking up github user of: {email}{FETCHING:#}");
+                            FETCHES.fetch_add(1, Ordering::Relaxed);
+                            match users_by_email.by_email(email).await {
+                                Ok(user) => {
+                                    write_cached(&key, &user).await;
+                                    user
+                                }
+                                Err(e) => {
+                                    warn!("failed to look up github user of {email}: {e}");
+                                    None
+                                }
+                            }
+                        }
+                    };
+                users.insert(email.clone(), user);
+            }
+
+            let Some(Some((gh_login, url, avatar_url))) = users.get(email) else {
+                continue;
+            };
+            for entry in contributions.get_mut(name).into_iter().flatten() {
+                if &entry.0 == login && entry.1.is_empty() {
+                    status!("unifying {login} of {src} with github user {gh_login}");
+                    entry.0.clone_from(gh_login);
+                    entry.1.clone_from(url);
+                    entry.2.clone_from(avatar_url);
+                }
+            }
+        }
+    }
+}
+
+/// Notes a source that was fetched yet credits nobody, e.g. an empty repository
+fn no_contributors(src: &str, crates: &BTreeSet<String>) {
+    warn!("{src} ({}) has no contributors", from_crates(crates));
+    if let Ok(mut empty) = EMPTY_SOURCES.lock() {
+        empty.insert(src.to_string());
+    }
+}
+
+/// Key sources processed by a run are cached under, for --only-new,
+/// per project and options changing what's fetched for a source
+fn processed_key(args: &Args) -> String {
+    let projects = Vec::from_iter(
+        args.path
+            .iter()
+            .map(|p| std::fs::canonicalize(p).unwrap_or(p.clone())),
+    );
+    let options = serde_json::json!({
+        "projects": projects,
+        "include_forks": args.include_forks,
+        "shallow": args.shallow,
+        "stats": args.stats,
+        "co_authors": args.co_authors,
+        "history": args.history,
+        "sponsors": args.sponsors,
+        "graphql": args.graphql,
+        "emails": args.unify_by_email,
+        "source_resolver": args.source_resolver,
+        "gitlab_hosts": args.gitlab_hosts,
+        "local_repos": args.local_repos,
+    });
+    let (_, digest) = cacache::Integrity::from(options.to_string()).to_hex();
+    format!("{PROCESSED_SOURCES_KEY}, {digest}")
+}
+
+/// Key crates.io data of a dependency set is cached under, the sorted crates and locked
+/// crate@version pairs as they are, so that it changes along with manifests and lock files
+fn resolved_key(crates: &BTreeSet<String>, locked: &BTreeMap<String, String>) -> String {
+    let crates = Vec::from_iter(crates.iter().map(String::as_str)).join(",");
+    let locked = Vec::from_iter(
+        locked
+            .iter()
+            .map(|(name, version)| format!("{name}@{version}")),
+    );
+    format!("crates-io resolved, {crates}; {}", locked.join(","))
+}
+
+/// Receives until the channel is closed or deadline is reached
+async fn recv_until<T>(rx: &mut UnboundedReceiver<T>, deadline: Option<Instant>) -> Option<T> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, rx.recv())
+            .await
+            .ok()
+            .flatten(),
+        None => rx.recv().await,
+    }
+}
+
+/// Awaits a fetching task, aborting it once deadline is reached
+async fn finish_fetching(
+    task: JoinHandle<anyhow::Result<()>>,
+    deadline: Option<Instant>,
+) -> anyhow::Result<()> {
+    if deadline.is_some_and(|d| Instant::now() >= d) {
+        task.abort();
+    }
+
+    match task.await {
+        Err(e) if e.is_cancelled() => Ok(()),
+        r => r?,
+    }
+}
+
+fn plural_helper(
+    h: &handlebars::Helper,
+    _: &Handlebars,
+    _: &handlebars::Context,
+    _: &mut handlebars::RenderContext,
+    out: &mut dyn handlebars::Output,
+) -> handlebars::HelperResult {
+    let count = h
+        .param(0)
+        .and_then(|p| p.value().as_number().and_then(|p| p.as_u64()))
+        .ok_or(handlebars::RenderErrorReason::MissingVariable(Some(
+            "expected count param".to_string(),
+        )))?;
+
+    let singular = h.param(1).and_then(|p| p.value().as_str()).ok_or(
+        handlebars::RenderErrorReason::MissingVariable(Some("expected singular param".to_string())),
+    )?;
+
+    if count == 1 {
+        out.write(singular)?;
+    } else if let Some(plural) = h.param(2).and_then(|p| p.value().as_str()) {
+        out.write(plural)?;
+    } else {
+        out.write(pluralize(singular).as_str())?;
+    }
+
+    Ok(())
+}
+
+fn slug_helper(
+    h: &handlebars::Helper,
+    _: &Handlebars,
+    _: &handlebars::Context,
+    _: &mut handlebars::RenderContext,
+    out: &mut dyn handlebars::Output,
+) -> handlebars::HelperResult {
+    let text = h.param(0).and_then(|p| p.value().as_str()).ok_or(
+        handlebars::RenderErrorReason::MissingVariable(Some("expected text param".to_string())),
+    )?;
+
+    out.write(slugify(text).as_str())?;
+
+    Ok(())
+}
+
+fn markdown_helper(
+    h: &handlebars::Helper,
+    _: &Handlebars,
+    _: &handlebars::Context,
+    _: &mut handlebars::RenderContext,
+    out: &mut dyn handlebars::Output,
+) -> handlebars::HelperResult {
+    let text = h.param(0).and_then(|p| p.value().as_str()).ok_or(
+        handlebars::RenderErrorReason::MissingVariable(Some("expected text param".to_string())),
+    )?;
+
+    out.write(markdown_escape(text).as_str())?;
+
+    Ok(())
+}
+
+/// Text with characters markdown would format escaped, e.g. `_` of `a_b`
+fn markdown_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '~' | '|'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn rst_helper(
+    h: &handlebars::Helper,
+    _: &Handlebars,
+    _: &handlebars::Context,
+    _: &mut handlebars::RenderContext,
+    out: &mut dyn handlebars::Output,
+) -> handlebars::HelperResult {
+    let text = h.param(0).and_then(|p| p.value().as_str()).ok_or(
+        handlebars::RenderErrorReason::MissingVariable(Some("expected text param".to_string())),
+    )?;
+
+    out.write(rst_escape(text).as_str())?;
+
+    Ok(())
+}
+
+/// Escaped title underlined with the second param, as long as the title itself
+fn rst_heading_helper(
+    h: &handlebars::Helper,
+    _: &Handlebars,
+    _: &handlebars::Context,
+    _: &mut handlebars::RenderContext,
+    out: &mut dyn handlebars::Output,
+) -> handlebars::HelperResult {
+    let text = h.param(0).and_then(|p| p.value().as_str()).ok_or(
+        handlebars::RenderErrorReason::MissingVariable(Some("expected text param".to_string())),
+    )?;
+    let underline = h.param(1).and_then(|p| p.value().as_str()).ok_or(
+        handlebars::RenderErrorReason::MissingVariable(Some(
+            "expected underline param".to_string(),
+        )),
+    )?;
+
+    let title = rst_escape(text);
+    out.write(&format!(
+        "{title}\n{}",
+        underline.repeat(title.chars().count())
+    ))?;
+
+    Ok(())
+}
+
+/// Text with characters starting reStructuredText inline markup escaped, e.g. `_` of `a_`
+fn rst_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '|' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Anchor github generates for a markdown heading
+fn slugify(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+/// English plural of a word, for the two-param form of `plural` helper,
+/// irregular ones keep the capitalization of the word
+fn pluralize(singular: &str) -> String {
+    if let Some((_, plural)) = IRREGULAR_PLURALS
+        .iter()
+        .find(|(s, _)| s.eq_ignore_ascii_case(singular))
+    {
+        return if singular.len() > 1 && singular.chars().all(|c| c.is_uppercase()) {
+            plural.to_uppercase()
+        } else if singular.starts_with(char::is_uppercase) {
+            plural[..1].to_uppercase() + &plural[1..]
+        } else {
+            plural.to_string()
+        };
+    }
+
+    let lower = singular.to_lowercase();
+    let before_y = lower.strip_suffix('y').and_then(|s| s.chars().last());
+
+    if before_y.is_some_and(|c| !"aeiou".contains(c)) {
+        format!("{}ies", &singular[..singular.len() - 1])
+    } else if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|suffix| lower.ends_with(suffix))
+    {
+        format!("{singular}es")
+    } else {
+        format!("{singular}s")
+    }
+}
+
+/// Orders names with accented or differently cased letters next to their base letters,
+/// language specific rules, e.g. `ä` after `z` in swedish, aren't applied
+fn collation_key(name: &str) -> String {
+    name.nfd()
+        .filter(|c| !unicode_normalization::char::is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Canonical https url of a git remote given in scp-like (`git@host:owner/repo`),
+/// `ssh://` or `git://` form, ports of these are dropped as they don't apply to https
+fn normalize_git_url(url: &str) -> String {
+    let url = url.trim();
+    let url = url.strip_prefix("git+").unwrap_or(url);
+
+    let (host, path) = if let Some(rest) = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    {
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        (host, path)
+    } else if let Some(rest) = url
+        .strip_prefix("ssh://")
+        .or_else(|| url.strip_prefix("git://"))
+    {
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        (host.split(':').next().unwrap_or(host), path)
+    } else if let Some((authority, path)) = url.split_once(':').filter(|(a, _)| !a.contains('/')) {
+        (authority.rsplit('@').next().unwrap_or(authority), path)
+    } else {
+        return url.to_string();
+    };
+
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+
+    format!("https://{host}/{path}")
+}
+
+/// Whether the cache falls back to the temp dir, shared with other users
+fn temp_cache() -> bool {
+    CACHE_DIR.get().is_none() && dirs::cache_dir().is_none()
+}
+
+fn cache_path() -> PathBuf {
+    static WARNING: Once = Once::new();
+
+    let mut path = CACHE_DIR
+        .get()
+        .cloned()
+        .or_else(dirs::cache_dir)
+        .unwrap_or_else(|| {
+            let dir = std::env::temp_dir();
+            WARNING.call_once(|| {
+                warn!(
+                    "Could not determine cache directory, caching in {} instead, use --cache-dir to choose another",
+                    dir.display()
+                )
+            });
+            dir
+        });
+    path.push(CACHE_NAME);
+    path
+}
+
+async fn read_cached<T>(key: &str) -> Option<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let path = cache_path();
+
+    if let Some(max) = MAX_CACHE_AGE.get().filter(|_| key != TOKEN_CACHE_KEY) {
+        if cache_age(key).await.is_some_and(|age| age > *max) {
+            return None;
+        }
+    }
+
+    let d: Vec<u8> = cacache::read(&path, key).await.ok()?;
+
+    let data = match d.split_first() {
+        Some((&CACHE_SCHEMA_VERSION, data)) => serde_json::from_slice::<T>(data).ok(),
+        _ => None,
+    };
+
+    if data.is_none() {
+        status!("purging stale cache entry: {key}");
+        _ = cacache::remove(&path, key).await;
+    }
+
+    data
+}
+
+async fn read_cached_unless<T>(no_cache: bool, key: &str) -> Option<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    if no_cache {
+        None
+    } else {
+        read_cached(key).await
+    }
+}
+
+/// Time since the entry was written
+async fn cache_age(key: &str) -> Option<Duration> {
+    let md = cacache::metadata(cache_path(), key).await.ok()??;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    now.checked_sub(Duration::from_millis(md.time as u64))
+}
+
+/// Key of the marker left by a permanently failed fetch
+fn failure_key(key: &str) -> String {
+    format!("{key}, failed")
+}
+
+/// Reason of a permanent failure of the source, while its marker hasn't expired
+async fn read_failure_unless(no_cache: bool, key: &str) -> Option<String> {
+    let key = failure_key(key);
+    let max = MAX_CACHE_AGE
+        .get()
+        .map_or(FAILURE_CACHE_AGE, |max| (*max).min(FAILURE_CACHE_AGE));
+
+    if cache_age(&key).await? > max {
+        remove_cached(&key).await;
+        return None;
+    }
+
+    read_cached_unless(no_cache, &key).await
+}
+
+async fn write_cached<T>(key: &str, data: T)
+where
+    T: serde::ser::Serialize,
+{
+    let path = cache_path();
+    if let Ok(data) = serde_json::to_vec(&data) {
+        let mut entry = Vec::with_capacity(data.len() + 1);
+        entry.push(CACHE_SCHEMA_VERSION);
+        entry.extend(data);
+        _ = cacache::write(path, key, entry).await;
+    }
+}
+
+async fn remove_cached(key: &str) {
+    _ = cacache::remove(cache_path(), key).await;
+}
+
+async fn export_cache(file: &Path) -> anyhow::Result<()> {
+    let mut entries = BTreeMap::new();
+
+    let path = cache_path();
+    let listed = if path.is_dir() {
+        Vec::from_iter(cacache::list_sync(path))
+    } else {
+        vec![]
+    };
+
+    for md in listed {
+        let key = md?.key;
+        if key == TOKEN_CACHE_KEY {
+            continue;
+        }
+        if let Some(value) = read_cached::<serde_json::Value>(&key).await {
+            entries.insert(key, value);
+        }
+    }
+
+    status!("Exporting {} cache entries...", entries.len());
+
+    let export = CacheExport {
+        version: CACHE_SCHEMA_VERSION,
+        entries,
+    };
+    fs::write(file, serde_json::to_vec(&export)?).await?;
+
+    anyhow::Ok(())
+}
+
+async fn import_cache(file: &Path) -> anyhow::Result<()> {
+    let export: CacheExport = serde_json::from_slice(fs::read(file).await?.as_slice())?;
+
+    if export.version != CACHE_SCHEMA_VERSION {
+        anyhow::bail!(
+            "cache export has schema version {}, expected {CACHE_SCHEMA_VERSION}",
+            export.version
+        );
+    }
+
+    status!("Importing {} cache entries...", export.entries.len());
+
+    for (key, value) in export.entries {
+        write_cached(&key, value).await;
+    }
+
+    anyhow::Ok(())
+}
+
+async fn clear_cache() -> anyhow::Result<()> {
+    cacache::clear(cache_path()).await?;
+    anyhow::Ok(())
+}
+
+/// Cargo.lock of a project or its workspace
+#[derive(Deserialize)]
+struct CargoLock {
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+#[derive(Deserialize)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    source: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct PackageMetadata {
+    acknowledgements: Option<MetadataAcknowledgements>,
+}
+
+/// `[package.metadata.acknowledgements]` table of a dependency
+#[derive(Deserialize)]
+struct MetadataAcknowledgements {
+    #[serde(default)]
+    contributors: Vec<MetadataContributor>,
+}
+
+#[derive(Deserialize)]
+struct MetadataContributor {
+    name: String,
+    #[serde(default)]
+    url: String,
+    contributions: Option<u32>,
+}
+
+/// Cargo.lock of the project, or of the workspace it belongs to
+fn cargo_lock(path: &Path) -> Option<CargoLock> {
+    let dir = if path.is_file() {
+        path.parent().unwrap_or(path)
+    } else {
+        path
+    };
+
+    dir.ancestors()
+        .map(|d| d.join("Cargo.lock"))
+        .find(|lock| lock.is_file())
+        .and_then(|lock| std::fs::read_to_string(lock).ok())
+        .and_then(|lock| toml::from_str::<CargoLock>(&lock).ok())
+}
+
+/// Locked versions of crates, the latest one when several are locked
+fn locked_versions(paths: &[PathBuf]) -> BTreeMap<String, String> {
+    let mut versions = BTreeMap::new();
+
+    // the first project locking a crate has its say
+    for lock in paths.iter().filter_map(|path| cargo_lock(path)) {
+        let mut locked = BTreeMap::new();
+        for package in lock.package {
+            // lock files list versions of a crate in ascending order
+            locked.insert(package.name, package.version);
+        }
+        for (name, version) in locked {
+            versions.entry(name).or_insert(version);
+        }
+    }
+
+    versions
+}
+
+/// Contributors credited by the authors of crates in their package metadata,
+/// read from manifests of the locked versions cargo downloaded from crates.io,
+/// crates cargo hasn't downloaded are named in a warning
+fn metadata_credits<'a>(
+    path: &Path,
+    crates: impl Iterator<Item = &'a String>,
+) -> BTreeMap<String, Vec<MetadataContributor>> {
+    let mut credits = BTreeMap::new();
+
+    let Some(lock) = cargo_lock(path) else {
+        return credits;
+    };
+
+    let indexes = Vec::from_iter(
+        std::env::var_os("CARGO_HOME")
+            .map(PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|home| home.join(".cargo")))
+            .map(|home| home.join("registry").join("src"))
+            .and_then(|src| std::fs::read_dir(src).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path()),
+    );
+    let mut missing = vec![];
+
+    for crate_name in crates {
+        let mut manifests = lock
+            .package
+            .iter()
+            .filter(|p| &p.name == crate_name)
+            .flat_map(|p| {
+                indexes
+                    .iter()
+                    .map(move |index| index.join(format!("{}-{}/Cargo.toml", p.name, p.version)))
+            })
+            .filter_map(|manifest| std::fs::read(manifest).ok())
+            .peekable();
+
+        if manifests.peek().is_none() {
+            let from_registry = |p: &LockedPackage| {
+                p.source
+                    .as_deref()
+                    .is_some_and(|s| s.starts_with("registry+") || s.starts_with("sparse+"))
+            };
+            if lock
+                .package
+                .iter()
+                .any(|p| &p.name == crate_name && from_registry(p))
+            {
+                missing.push(crate_name.as_str());
+            }
+            continue;
+        }
+
+        for manifest in manifests {
+            if let Some(acknowledgements) =
+                Manifest::<PackageMetadata>::from_slice_with_metadata(&manifest)
+                    .ok()
+                    .and_then(|m| m.package)
+                    .and_then(|p| p.metadata)
+                    .and_then(|m| m.acknowledgements)
+            {
+                credits.insert(crate_name.clone(), acknowledgements.contributors);
+                break;
+            }
+        }
+    }
+
+    if !missing.is_empty() {
+        warn!(
+            "package metadata of {} isn't downloaded, run cargo fetch to credit contributors listed there",
+            missing.join(", ")
+        );
+    }
+
+    credits
+}
+
+/// Dependencies declared in the manifest and its workspace members,
+/// members failing to load are skipped unless there's nowhere to collect them
+fn manifest_deps(
+    path: &Path,
+    depth: &Breadth,
+    with_members: bool,
+    features: &FeatureFlags,
+    mut skipped: Option<&mut Vec<PathBuf>>,
+) -> anyhow::Result<Vec<(String, Dependency)>> {
+    let manifest = load_manifest(path)?;
+
+    // with features requested, only optional deps they enable are included
+    let enabled = enabled_optional_deps(&manifest, features);
+    let activated = |(k, d): &(&String, &Dependency)| match &enabled {
+        Some(enabled) => !d.optional() || enabled.contains(k.as_str()),
+        None => true,
+    };
+
+    // optional deps enabled by default features are compiled in a default build
+    let compiled = enabled
+        .clone()
+        .unwrap_or_else(|| features_optional_deps(&manifest, ["default"].into_iter()));
+
+    let mut deps: Vec<_> = match depth {
+        Breadth::NonOpt => manifest
+            .dependencies
+            .iter()
+            .filter(|(k, d)| !d.optional() || compiled.contains(k.as_str()))
+            .map(|(k, d)| (k.clone(), d.clone()))
+            .collect(),
+        Breadth::All => manifest
+            .dependencies
+            .iter()
+            .filter(activated)
+            .map(|(k, d)| (k.clone(), d.clone()))
+            .collect(),
+        Breadth::BuildAndDev => manifest
+            .dependencies
+            .iter()
+            .chain(manifest.dev_dependencies.iter())
+            .chain(manifest.build_dependencies.iter())
+            .filter(activated)
+            .map(|(k, d)| (k.clone(), d.clone()))
+            .collect(),
+    };
+
+    // members are found next to the manifest, of which there's none on stdin
+    let piped_workspace = is_stdin(path) && manifest.workspace.is_some();
+    if with_members && piped_workspace {
+        warn!("workspace members can't be found from a manifest on stdin, analyzing its own dependencies only");
+    }
+    let with_members = with_members && !piped_workspace;
+
+    if let Some(workspace) = manifest.workspace {
+        if with_members {
+            // members inherit what they use of workspace dependencies,
+            // resolved against the workspace table when their manifests are read
+            for member in workspace.members.iter() {
+                let member_path = path.join(member);
+                match manifest_deps(
+                    &member_path,
+                    depth,
+                    with_members,
+                    features,
+                    skipped.as_deref_mut(),
+                ) {
+                    Ok(member_deps) => deps.extend(member_deps),
+                    Err(e) => match skipped.as_deref_mut() {
+                        Some(skipped) => {
+                            warn!("skipping workspace member {}: {e}", member_path.display());
+                            skipped.push(member_path);
+                        }
+                        None => return Err(e),
+                    },
+                }
+            }
+        } else {
+            match depth {
+                Breadth::BuildAndDev => deps.extend(
+                    workspace
+                        .dependencies
+                        .iter()
+                        .map(|(k, d)| (k.clone(), d.clone())),
+                ),
+                _ => deps.extend(
+                    workspace
+                        .dependencies
+                        .iter()
+                        .filter(|d| !d.1.optional())
+                        .map(|(k, d)| (k.clone(), d.clone())),
+                ),
+            }
+        }
+    }
+
+    Ok(deps)
+}
+
+/// Prints a diff and fails when the existing file differs from what would be written
+fn check_up_to_date(path: &Path, existing: &str, expected: &str) -> anyhow::Result<()> {
+    if existing != expected {
+        let path = path.display().to_string();
+        print!(
+            "{}",
+            similar::TextDiff::from_lines(existing, expected)
+                .unified_diff()
+                .header(&path, "generated")
+        );
+        anyhow::bail!("{path} is stale, run acknowledge to regenerate it");
+    }
+    status!("{} is up to date", path.display());
+
+    Ok(())
+}
+
+/// Manifest at path, or in the directory at path, or piped in on stdin for `-`
+fn load_manifest(path: &Path) -> anyhow::Result<Manifest> {
+    if is_stdin(path) {
+        let piped = STDIN_MANIFEST
+            .get()
+            .ok_or_else(|| anyhow::anyhow!("no manifest was read from stdin"))?;
+        return Ok(Manifest::from_str(piped)?);
+    }
+
+    Manifest::from_path(path).or_else(|_| {
+        let path = path.join("Cargo.toml");
+        Ok(Manifest::from_path(path.as_path())?)
+    })
+}
+
+fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Directory outputs go to by default, the current one for a manifest on stdin
+fn project_dir(path: &Path) -> &Path {
+    if is_stdin(path) {
+        Path::new("")
+    } else {
+        path
+    }
+}
+
+/// Path of the workspace member at path named package, or path itself if it's that package
+fn workspace_member(path: &Path, package: &str) -> Option<PathBuf> {
+    let manifest = load_manifest(path).ok()?;
+
+    if manifest.package.as_ref().is_some_and(|p| p.name == package) {
+        return Some(path.to_path_buf());
+    }
+
+    manifest
+        .workspace?
+        .members
+        .iter()
+        .find_map(|member| workspace_member(&path.join(member), package))
+}
+
+/// Dependencies projects and members share, differing only in versions or features,
+/// are kept once
+fn dedup_deps(deps: &mut Vec<(String, Dependency)>) {
+    // sorted by source too, so that duplicates end up adjacent
+    deps.sort_by(|(name_1, dep_1), (name_2, dep_2)| {
+        (name_1, dependency_source(dep_1)).cmp(&(name_2, dependency_source(dep_2)))
+    });
+    deps.dedup_by(|(name_1, dep_1), (name_2, dep_2)| {
+        name_1 == name_2 && dependency_source(dep_1) == dependency_source(dep_2)
+    });
+}
+
+/// Where a dependency comes from, as its git url and pinned ref, path, registry and package name,
+/// none of which are set for crates.io dependencies given by version
+fn dependency_source(dep: &Dependency) -> [Option<&str>; 7] {
+    let detail = dep.detail();
+    let field = |f: fn(&cargo_toml::DependencyDetail) -> &Option<String>| {
+        detail.and_then(|d| f(d).as_deref())
+    };
+
+    [
+        field(|d| &d.git),
+        field(|d| &d.rev),
+        field(|d| &d.tag),
+        field(|d| &d.branch),
+        field(|d| &d.path),
+        field(|d| &d.registry),
+        field(|d| &d.package),
+    ]
+}
+
+/// `[source]` tables of a `.cargo/config.toml`
+#[derive(Deserialize, Default)]
+struct CargoConfig {
+    #[serde(default)]
+    source: BTreeMap<String, SourceConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct SourceConfig {
+    replace_with: Option<String>,
+    directory: Option<PathBuf>,
+}
+
+enum CratesIoReplacement {
+    /// Directory of vendored crates
+    Vendored(PathBuf),
+    /// Any other registry by its name in cargo config
+    Registry(String),
+}
+
+/// Source replacing crates-io in cargo configs of the project, its parents or cargo home,
+/// the nearest config defining a source takes precedence
+fn crates_io_replacement(path: &Path) -> Option<CratesIoReplacement> {
+    let dir = if path.is_file() {
+        path.parent().unwrap_or(path)
+    } else {
+        path
+    };
+    let dir = std::fs::canonicalize(dir).unwrap_or(dir.to_path_buf());
+
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".cargo")));
+
+    let mut sources: BTreeMap<String, (PathBuf, SourceConfig)> = BTreeMap::new();
+    let configs = dir
+        .ancestors()
+        .map(|d| d.join(".cargo"))
+        .chain(cargo_home)
+        .flat_map(|d| [d.join("config.toml"), d.join("config")]);
+
+    for config in configs {
+        let Some(parsed) = std::fs::read_to_string(&config)
+            .ok()
+            .and_then(|c| toml::from_str::<CargoConfig>(&c).ok())
+        else {
+            continue;
+        };
+        // relative paths are relative to the directory containing `.cargo`
+        let base = config
+            .parent()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        for (name, source) in parsed.source {
+            sources.entry(name).or_insert((base.clone(), source));
+        }
+    }
+
+    let name = sources
+        .get("crates-io")
+        .and_then(|(_, s)| s.replace_with.clone())?;
+
+    match sources.get(&name) {
+        Some((
+            base,
+            SourceConfig {
+                directory: Some(directory),
+                ..
+            },
+        )) => Some(CratesIoReplacement::Vendored(base.join(directory))),
+        _ => Some(CratesIoReplacement::Registry(name)),
+    }
+}
+
+/// Ref a github repository of a git dependency is pinned to, rev, tag or branch in cargo's order,
+/// so that contributors are counted up to it, other forges are counted at HEAD
+fn pinned_ref(git: &str, detail: &cargo_toml::DependencyDetail) -> Option<String> {
+    let pinned = detail
+        .rev
+        .as_ref()
+        .or(detail.tag.as_ref())
+        .or(detail.branch.as_ref())?;
+
+    if git.starts_with(GITHUB_BASE) {
+        Some(pinned.clone())
+    } else {
+        status!("{git} is pinned to {pinned}, its contributors are counted at HEAD");
+        None
+    }
+}
+
+/// Repositories of vendored crates by their names
+fn vendored_repositories(dir: &Path) -> BTreeMap<String, String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return BTreeMap::new();
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| Manifest::from_path(e.path().join("Cargo.toml")).ok())
+        .filter_map(|m| m.package)
+        .filter_map(|p| {
+            let repository = p.repository.and_then(|r| r.get().ok().cloned())?;
+            Some((p.name, repository))
+        })
+        .collect()
+}
+
+/// Dependencies listed in a package.json, optional and dev ones as breadth tells
+fn package_json_deps(path: &Path, breadth: &Breadth) -> anyhow::Result<BTreeSet<String>> {
+    let package: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)?;
+    let sections: &[&str] = match breadth {
+        Breadth::NonOpt => &["dependencies"],
+        Breadth::All => &["dependencies", "optionalDependencies"],
+        Breadth::BuildAndDev => &["dependencies", "optionalDependencies", "devDependencies"],
+    };
+
+    Ok(sections
+        .iter()
+        .filter_map(|section| package.get(section)?.as_object())
+        .flat_map(|deps| deps.keys().cloned())
+        .collect())
+}
+
+/// Crate data on crates.io
+#[derive(Deserialize)]
+struct CratesIoCrate {
+    #[serde(rename = "crate")]
+    crate_data: CratesIoCrateData,
+    categories: Vec<CratesIoCategory>,
+    versions: Vec<CratesIoVersion>,
+}
+
+#[derive(Deserialize)]
+struct CratesIoCrateData {
+    repository: Option<String>,
+    downloads: u64,
+    max_version: String,
+}
+
+#[derive(Deserialize)]
+struct CratesIoCategory {
+    category: String,
+}
+
+#[derive(Deserialize)]
+struct CratesIoVersion {
+    num: String,
+    license: Option<String>,
+}
+
+/// Crates.io search results
+#[derive(Deserialize)]
+struct CratesIoPage {
+    crates: Vec<CratesIoName>,
+}
+
+#[derive(Deserialize)]
+struct CratesIoName {
+    name: String,
+}
+
+/// Data of a crate on crates.io, none when the crate isn't published there
+async fn crates_io_crate(
+    client: &reqwest::Client,
+    api: &str,
+    name: &str,
+) -> anyhow::Result<Option<CratesIoCrate>> {
+    let res = client::get(client, &format!("{api}/crates/{name}")).await?;
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    Ok(Some(res.error_for_status()?.json().await?))
+}
+
+/// Names of up to 3 crates on crates.io matching a search for the name,
+/// none when the search fails
+async fn crates_io_similar(client: &reqwest::Client, api: &str, name: &str) -> Vec<String> {
+    let url = format!("{api}/crates?q={name}&per_page=3");
+    let page = async {
+        client::get(client, &url)
+            .await?
+            .error_for_status()?
+            .json::<CratesIoPage>()
+            .await
+    };
+
+    page.await
+        .map(|page| page.crates.into_iter().map(|c| c.name).collect())
+        .unwrap_or_default()
+}
+
+/// Repository of the latest version of a package on the npm registry,
+/// none when the package or its repository isn't known
+async fn npm_repository(client: &reqwest::Client, package: &str) -> anyhow::Result<Option<String>> {
+    let res = client::get(client, &format!("{NPM_REGISTRY}/{package}/latest")).await?;
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        warn!("{package} not found on npm, skipping");
+        return Ok(None);
+    }
+    let latest: serde_json::Value = res.error_for_status()?.json().await?;
+
+    Ok(latest.get("repository").and_then(npm_repository_url))
+}
+
+/// Url of a repository field of package.json, given as an url, an object with one,
+/// or a `github:`, `gitlab:`, `bitbucket:` or bare `owner/repo` shorthand
+fn npm_repository_url(repository: &serde_json::Value) -> Option<String> {
+    let url = repository
+        .as_str()
+        .or_else(|| repository.get("url")?.as_str())?;
+
+    Some(match url.split_once(':') {
+        Some(("github", path)) => format!("{GITHUB_BASE}/{path}"),
+        Some(("gitlab", path)) => format!("https://gitlab.com/{path}"),
+        Some(("bitbucket", path)) => format!("https://bitbucket.org/{path}"),
+        None if url.matches('/').count() == 1 => format!("{GITHUB_BASE}/{url}"),
+        _ => url.to_string(),
+    })
+}
+
+/// Names of authors of the project and its workspace members, without emails
+fn manifest_authors(path: &Path, with_members: bool) -> anyhow::Result<BTreeSet<String>> {
+    let manifest = load_manifest(path)?;
+
+    let mut authors = BTreeSet::new();
+    let listed = manifest
+        .package
+        .iter()
+        .filter_map(|p| p.authors.get().ok())
+        .chain(
+            manifest
+                .workspace
+                .iter()
+                .filter_map(|w| w.package.as_ref()?.authors.as_ref()),
+        )
+        .flatten();
+
+    for author in listed {
+        let name = author
+            .split_once(" <")
+            .map_or(author.as_str(), |(name, _)| name);
+        authors.insert(name.trim().to_string());
+    }
+
+    if let Some(workspace) = manifest
+        .workspace
+        .filter(|_| with_members && !is_stdin(path))
+    {
+        for member in workspace.members.iter() {
+            // broken members were reported as skipped already
+            authors.extend(manifest_authors(&path.join(member), with_members).unwrap_or_default());
+        }
+    }
+
+    Ok(authors)
+}
+
+/// Optional dependencies enabled by requested features,
+/// `None` if no features were requested
+fn enabled_optional_deps(manifest: &Manifest, flags: &FeatureFlags) -> Option<HashSet<String>> {
+    if flags.features.is_empty() && !flags.all_features && !flags.no_default_features {
+        return None;
+    }
+
+    if flags.all_features {
+        return Some(
+            manifest
+                .dependencies
+                .iter()
+                .chain(manifest.build_dependencies.iter())
+                .filter(|(_, d)| d.optional())
+                .map(|(k, _)| k.clone())
+                .collect(),
+        );
+    }
+
+    let requested = flags
+        .features
+        .iter()
+        .map(String::as_str)
+        .chain((!flags.no_default_features).then_some("default"));
+
+    Some(features_optional_deps(manifest, requested))
+}
+
+/// Optional deps enabled by features, directly or through other features
+fn features_optional_deps<'a>(
+    manifest: &Manifest,
+    requested: impl Iterator<Item = &'a str>,
+) -> HashSet<String> {
+    let mut enabled = HashSet::new();
+
+    // virtual manifests have no features
+    if manifest.package.is_none() {
+        return enabled;
+    }
+
+    let resolved = cargo_toml::features::Resolver::new().parse(manifest);
+
+    for name in requested {
+        if let Some(feature) = resolved.features.get(name) {
+            let (_, deps) = feature.enables_recursive(&resolved.features);
+            enabled.extend(deps.into_keys().map(String::from));
+        }
+    }
+
+    enabled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_matches_template_data() {
+        let data = TemplateData {
+            emoji: true,
+            ..Default::default()
+        };
+        let data = serde_json::to_value(data).unwrap();
+        let schema = template_schema();
+
+        let fields = Vec::from_iter(data.as_object().unwrap().keys());
+        let properties = Vec::from_iter(schema["properties"].as_object().unwrap().keys());
+        assert_eq!(fields, properties);
+    }
+
+    #[test]
+    fn replaces_between_markers() {
+        let existing =
+            "# Thanks\n\nintro\n<!-- acknowledge:begin -->\nold\n<!-- acknowledge:end -->\noutro\n";
+
+        assert_eq!(
+            between_markers(existing, "new\n").as_deref(),
+            Some("# Thanks\n\nintro\n<!-- acknowledge:begin -->\nnew\n<!-- acknowledge:end -->\noutro\n")
+        );
+        assert_eq!(between_markers("no markers", "new\n"), None);
+    }
+
+    #[test]
+    fn slugifies_like_github() {
+        assert_eq!(slugify("serde_json"), "serde_json");
+        assert_eq!(slugify("Contributors of `tokio`"), "contributors-of-tokio");
+        assert_eq!(slugify(" Thank you 🙏"), "thank-you-");
+    }
+
+    #[test]
+    fn escapes_markdown_in_names() {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("plural", Box::new(plural_helper));
+        handlebars.register_helper("md", Box::new(markdown_helper));
+        handlebars
+            .register_template_string("template", TEMPLATE)
+            .unwrap();
+
+        let data = TemplateData {
+            thank: vec![ThankData::NameAndCount {
+                name: "a_b*c".to_string(),
+                profile_url: "https://example.com/a_b".to_string(),
+                avatar_url: String::new(),
+                count: 3,
+                percent: None,
+                history: None,
+            }],
+            bots: BTreeSet::from([("[bot]".to_string(), String::new())]),
+            emoji: true,
+            ..Default::default()
+        };
+        let rendered = handlebars.render("template", &data).unwrap();
+
+        assert!(rendered.contains("**[a\\_b\\*c](https://example.com/a_b)**"));
+        assert!(rendered.contains("**\\[bot\\]**"));
+        assert_eq!(markdown_escape("`tick` #1 <x>"), "\\`tick\\` \\#1 \\<x\\>");
+    }
+
+    #[test]
+    fn dedups_interleaved_dependency_sources() {
+        let git = |url: &str| {
+            Dependency::Detailed(Box::new(cargo_toml::DependencyDetail {
+                git: Some(url.to_string()),
+                ..Default::default()
+            }))
+        };
+        let mut deps = vec![
+            ("foo".to_string(), git("https://github.com/a/foo")),
+            ("foo".to_string(), git("https://github.com/b/foo")),
+            ("foo".to_string(), git("https://github.com/a/foo")),
+            ("foo".to_string(), Dependency::Simple("1".to_string())),
+        ];
+        dedup_deps(&mut deps);
+
+        assert_eq!(deps.len(), 3);
+    }
+
+    #[test]
+    fn pins_github_refs_apart_from_urls() {
+        let detail = cargo_toml::DependencyDetail {
+            tag: Some("v1".to_string()),
+            branch: Some("main".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            pinned_ref("https://github.com/a/foo", &detail).as_deref(),
+            Some("v1")
+        );
+        assert_eq!(pinned_ref("https://gitlab.com/a/foo", &detail), None);
+        assert_eq!(
+            pinned_ref("https://github.com/a/foo", &Default::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn pluralizes_irregular_nouns_keeping_case() {
+        assert_eq!(pluralize("person"), "people");
+        assert_eq!(pluralize("Person"), "People");
+        assert_eq!(pluralize("CHILD"), "CHILDREN");
+        assert_eq!(pluralize("Library"), "Libraries");
+    }
+
+    #[test]
+    fn collates_accents_and_case() {
+        let mut names = vec!["Zoë", "émile", "Eva", "zach"];
+        names.sort_by_key(|n| collation_key(n));
+        assert_eq!(names, vec!["émile", "Eva", "zach", "Zoë"]);
+    }
+
+    #[test]
+    fn normalizes_https_urls() {
+        assert_eq!(
+            normalize_git_url("https://github.com/owner/repo"),
+            "https://github.com/owner/repo"
+        );
+        assert_eq!(
+            normalize_git_url("https://github.com/owner/repo.git/"),
+            "https://github.com/owner/repo"
+        );
+        assert_eq!(
+            normalize_git_url("git+https://gitlab.com/owner/repo.github.io.git"),
+            "https://gitlab.com/owner/repo.github.io"
+        );
+        assert_eq!(
+            normalize_git_url("https://git.example.com:8443/owner/repo"),
+            "https://git.example.com:8443/owner/repo"
+        );
+    }
+
+    #[test]
+    fn normalizes_scp_like_urls() {
+        assert_eq!(
+            normalize_git_url("git@github.com:owner/repo.git"),
+            "https://github.com/owner/repo"
+        );
+        assert_eq!(
+            normalize_git_url("git.example.com:owner/repo"),
+            "https://git.example.com/owner/repo"
+        );
+    }
+
+    #[test]
+    fn normalizes_ssh_urls() {
+        assert_eq!(
+            normalize_git_url("ssh://git@github.com/owner/repo.git"),
+            "https://github.com/owner/repo"
+        );
+        assert_eq!(
+            normalize_git_url("ssh://git@git.example.com:2222/owner/repo.git"),
+            "https://git.example.com/owner/repo"
+        );
+        assert_eq!(
+            normalize_git_url("git://git.example.com:9418/owner/repo"),
+            "https://git.example.com/owner/repo"
+        );
+    }
+
+    #[test]
+    fn processed_key_follows_project_and_options() {
+        let key = |args: &[&str]| processed_key(&Args::parse_from(args));
+
+        assert_eq!(
+            key(&["acknowledge", "-p", "."]),
+            key(&["acknowledge", "-p", ".", "-f", "DepAndNames"])
+        );
+        assert_ne!(
+            key(&["acknowledge", "-p", "."]),
+            key(&["acknowledge", "-p", ".", "--shallow"])
+        );
+        assert_ne!(
+            key(&["acknowledge", "-p", "."]),
+            key(&["acknowledge", "-p", "tests/fixtures/project"])
+        );
+    }
+
+    #[test]
+    fn resolved_key_follows_lock_files() {
+        let crates = BTreeSet::from(["foo".to_string()]);
+        let locked = BTreeMap::from([("foo".to_string(), "1.0.0".to_string())]);
+        let bumped = BTreeMap::from([("foo".to_string(), "1.0.1".to_string())]);
+
+        assert_eq!(
+            resolved_key(&crates, &locked),
+            resolved_key(&crates, &locked)
+        );
+        assert_ne!(
+            resolved_key(&crates, &locked),
+            resolved_key(&crates, &bumped)
+        );
+        assert_ne!(
+            resolved_key(&crates, &locked),
+            resolved_key(&BTreeSet::new(), &locked)
+        );
+        assert_eq!(
+            resolved_key(&crates, &locked),
+            "crates-io resolved, foo; foo@1.0.0"
+        );
+    }
+
+    #[test]
+    fn allows_default_and_given_hosts() {
+        let allowed = vec!["git.sr.ht".to_string()];
+
+        assert!(is_allowed_host("https://github.com/foo/bar", &allowed));
+        assert!(is_allowed_host("https://GitLab.com/foo/bar", &[]));
+        assert!(is_allowed_host("https://git.sr.ht/~foo/bar", &allowed));
+        assert!(!is_allowed_host("https://git.sr.ht/~foo/bar", &[]));
+        assert!(!is_allowed_host(
+            "https://github.com.evil.example/foo/bar",
+            &[]
+        ));
+        assert!(!is_allowed_host("foo/bar", &allowed));
+        assert!(is_allowed_host(
+            "https://git.example.com/foo/bar",
+            &["*".to_string()]
+        ));
+    }
+
+    #[test]
+    fn keeps_all_contributors_settings() {
+        let existing = br#"{"projectName": "fixture", "contributors": [{"login": "gone"}]}"#;
+        let listed = BTreeMap::from([
+            ("bob", ("https://github.com/bob", "", 3)),
+            (
+                "alice",
+                ("https://github.com/alice", "https://avatars/1", 5),
+            ),
+            ("dave", ("", "", 9)),
+        ]);
+
+        let rc: serde_json::Value =
+            serde_json::from_str(&all_contributors_rc(Some(existing), &listed).unwrap()).unwrap();
+
+        assert_eq!(
+            rc,
+            serde_json::json!({
+                "projectName": "fixture",
+                "contributors": [
+                    { "login": "gone" },
+                    {
+                        "login": "alice",
+                        "name": "alice",
+                        "avatar_url": "https://avatars/1",
+                        "profile": "https://github.com/alice",
+                        "contributions": ["code"]
+                    },
+                    {
+                        "login": "bob",
+                        "name": "bob",
+                        "profile": "https://github.com/bob",
+                        "contributions": ["code"]
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn merges_all_contributors_by_login() {
+        let existing = br#"{"contributors": [
+            {"login": "Alice", "name": "Alice A.", "contributions": ["doc", "design"]},
+            {"login": "bob", "name": "Bob", "contributions": ["code"]}
+        ]}"#;
+        let listed = BTreeMap::from([
+            (
+                "alice",
+                ("https://github.com/alice", "https://avatars/1", 5),
+            ),
+            ("bob", ("https://github.com/bob", "", 3)),
+        ]);
+
+        let rc: serde_json::Value =
+            serde_json::from_str(&all_contributors_rc(Some(existing), &listed).unwrap()).unwrap();
+
+        assert_eq!(
+            rc,
+            serde_json::json!({
+                "contributors": [
+                    {
+                        "login": "Alice",
+                        "name": "Alice A.",
+                        "avatar_url": "https://avatars/1",
+                        "profile": "https://github.com/alice",
+                        "contributions": ["doc", "design", "code"]
+                    },
+                    { "login": "bob", "name": "Bob", "contributions": ["code"], "profile": "https://github.com/bob" }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn parses_output_targets() {
+        let targets = output_targets(
+            Format::NameAndCount,
+            &[
+                PathBuf::from("DepAndNames=docs/thanks.md"),
+                PathBuf::from("a=b.md"),
+            ],
+        );
+
+        assert!(matches!(
+            targets.as_slice(),
+            [(Format::DepAndNames, Some(p1)), (Format::NameAndCount, Some(p2))]
+                if p1 == Path::new("docs/thanks.md") && p2 == Path::new("a=b.md")
+        ));
+        assert!(matches!(
+            output_targets(Format::NameAndDeps, &[]).as_slice(),
+            [(Format::NameAndDeps, None)]
+        ));
+    }
+
+    #[test]
+    fn detects_ambiguous_repositories() {
+        assert!(!is_ambiguous("https://github.com/owner/repo"));
+        assert!(!is_ambiguous("https://gitlab.com/group/subgroup/repo"));
+        assert!(is_ambiguous("https://github.com/owner"));
+        assert!(is_ambiguous("https://github.com/owner/"));
+        assert!(is_ambiguous("owner/repo"));
+    }
+
+    #[test]
+    fn reads_npm_repositories() {
+        let url = |repository: serde_json::Value| npm_repository_url(&repository);
+
+        assert_eq!(
+            url(
+                serde_json::json!({ "type": "git", "url": "git+https://github.com/owner/repo.git" })
+            ),
+            Some("git+https://github.com/owner/repo.git".to_string())
+        );
+        assert_eq!(
+            url(serde_json::json!("github:owner/repo")),
+            Some("https://github.com/owner/repo".to_string())
+        );
+        assert_eq!(
+            url(serde_json::json!("owner/repo")),
+            Some("https://github.com/owner/repo".to_string())
+        );
+        assert_eq!(
+            url(serde_json::json!("gitlab:group/repo")),
+            Some("https://gitlab.com/group/repo".to_string())
+        );
+        assert_eq!(url(serde_json::json!({ "type": "git" })), None);
+    }
 }
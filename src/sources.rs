@@ -0,0 +1,1476 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use futures_util::future::join_all;
+use octocrab::{
+    models::{Contributor, Rate, RateLimit},
+    repos::RepoHandler,
+    Octocrab, Page,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::Mutex,
+    time::{sleep, Duration},
+};
+use unfmt_macros::unformat;
+
+use crate::{
+    client, prompt, read_cached, read_cached_unless, remove_cached, write_cached, Contribution,
+    STATUS_TO_STDERR, WAITING,
+};
+
+/// A forge to fetch contributors of a repository from
+#[async_trait::async_trait]
+pub trait ContributorSource: Send + Sync {
+    /// Name of the forge in progress messages
+    fn name(&self) -> &str;
+
+    /// Key contributions of the repository are cached under
+    fn cache_key(&self, repo: &str) -> String {
+        repo.to_string()
+    }
+
+    async fn fetch(&self, repo: &str) -> anyhow::Result<Vec<Contribution>>;
+
+    /// Number of repositories fetched together by fetch_many
+    fn batch_size(&self) -> usize {
+        1
+    }
+
+    /// Fetches the repositories concurrently, results are in their order
+    async fn fetch_many(&self, repos: &[String]) -> Vec<anyhow::Result<Vec<Contribution>>> {
+        join_all(repos.iter().map(|repo| self.fetch(repo))).await
+    }
+}
+
+pub struct GitHub {
+    client: Octocrab,
+    include_forks: bool,
+    shallow: bool,
+    stats: bool,
+    co_authors: bool,
+    history: Option<u32>,
+    sponsors: bool,
+    no_cache: bool,
+    /// Seconds all requests may wait for quota resets together
+    max_wait: Option<u64>,
+    /// Refs repositories of git dependencies are pinned to, counted up to them
+    pinned: BTreeMap<String, String>,
+    /// Core quota shared by all requests of the source, refreshed on reset,
+    /// and seconds waited for its resets so far
+    quota: Mutex<(Option<RateLimit>, u64)>,
+}
+
+/// Error of requests that would wait for the github quota longer than --max-wait allows
+#[derive(Debug)]
+pub struct WaitExceeded;
+
+impl fmt::Display for WaitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "waiting for github's rate limit would exceed --max-wait")
+    }
+}
+
+impl std::error::Error for WaitExceeded {}
+
+impl GitHub {
+    #[allow(clippy::too_many_arguments)] // one per option of the source
+    pub fn new(
+        client: Octocrab,
+        include_forks: bool,
+        shallow: bool,
+        stats: bool,
+        co_authors: bool,
+        history: Option<u32>,
+        sponsors: bool,
+        no_cache: bool,
+        max_wait: Option<u64>,
+        pinned: BTreeMap<String, String>,
+    ) -> Self {
+        Self {
+            client,
+            include_forks,
+            shallow,
+            stats,
+            co_authors,
+            history,
+            sponsors,
+            no_cache,
+            max_wait,
+            pinned,
+            quota: Mutex::new((None, 0)),
+        }
+    }
+
+    /// Reserves a request from the quota, waiting for its reset when exhausted,
+    /// requests wait in turn so that concurrent ones don't overshoot it
+    async fn reserve(&self) -> anyhow::Result<()> {
+        let mut quota = self.quota.lock().await;
+        let (limit, waited) = &mut *quota;
+        let current = match limit.take() {
+            Some(limit) => limit,
+            None => self.client.ratelimit().get().await?,
+        };
+
+        if current.resources.core.remaining == 0 {
+            let wait = (current.resources.core.reset as i64 - Utc::now().timestamp()).max(0) as u64;
+            if self.max_wait.is_some_and(|max| *waited + wait > max) {
+                *limit = Some(current);
+                return Err(WaitExceeded.into());
+            }
+            *waited += wait;
+        }
+
+        *limit = Some(gh_rate_limited(current, &self.client).await?);
+        Ok(())
+    }
+
+    /// Commit totals per author from the statistics api,
+    /// none when github can't provide them, e.g. for repos with too many commits
+    async fn contributor_stats(&self, owner: &str, name: &str) -> Option<Vec<StatsContributor>> {
+        let route = format!("/repos/{owner}/{name}/stats/contributors");
+
+        for _ in 0..STATS_POLLS {
+            self.reserve().await.ok()?;
+            let res = self.client._get(route.as_str()).await.ok()?;
+
+            // statistics are computed in the background on first request
+            if res.status() == StatusCode::ACCEPTED {
+                status!("waiting for github to compute statistics of {owner}/{name}...");
+                sleep(STATS_POLL_INTERVAL).await;
+                continue;
+            }
+
+            if res.status() != StatusCode::OK {
+                return None;
+            }
+
+            let body = self.client.body_to_string(res).await.ok()?;
+            return serde_json::from_str(&body).ok();
+        }
+
+        None
+    }
+
+    /// Contributors listed by github, all pages of them unless shallow
+    async fn list_contributors(
+        &self,
+        repo: &str,
+        (owner, name): (&str, &str),
+        repo_handler: &RepoHandler<'_>,
+        repo_name: &str,
+    ) -> anyhow::Result<Vec<Contribution>> {
+        // github answers 204 without a body for repositories without commits
+        let route = format!("/repos/{owner}/{name}/contributors");
+        let res = self.client._get(route.as_str()).await?;
+        if res.status() == StatusCode::NO_CONTENT {
+            return Ok(vec![]);
+        }
+        let res = octocrab::map_github_error(res).await?;
+        let first: Page<Contributor> = octocrab::FromResponse::from_response(res).await?;
+        let pages = first.number_of_pages().filter(|_| !self.shallow);
+        let mut contributors = first.items;
+
+        if let Some(pages) = pages {
+            // pages are cached as they arrive, so that an interrupted run resumes where it stopped
+            let key = self.cache_key(repo);
+
+            for page in 2..=pages {
+                let page_key = format!("{key}, page {page}");
+
+                if let Some(cached) =
+                    read_cached_unless::<Vec<Contributor>>(self.no_cache, &page_key).await
+                {
+                    contributors.extend(cached);
+                    continue;
+                }
+
+                self.reserve().await?;
+                let next = repo_handler.list_contributors().page(page).send().await?;
+                write_cached(&page_key, &next.items).await;
+                contributors.extend(next.items);
+            }
+
+            for page in 2..=pages {
+                remove_cached(&format!("{key}, page {page}")).await;
+            }
+        }
+
+        Ok(contributors
+            .into_iter()
+            .map(|c| {
+                (
+                    repo_name.to_string(),
+                    c.author.login,
+                    c.author.html_url.to_string(),
+                    c.author.avatar_url.to_string(),
+                    c.contributions,
+                )
+            })
+            .collect())
+    }
+
+    /// Co-authors credited in trailers of the latest commits, up to the ref when pinned,
+    /// by login when known
+    async fn co_authors(
+        &self,
+        repo_handler: &RepoHandler<'_>,
+        pinned: Option<&str>,
+    ) -> anyhow::Result<BTreeMap<(String, String), u32>> {
+        let mut co_authors = BTreeMap::new();
+
+        for page in 1..=CO_AUTHOR_PAGES {
+            self.reserve().await?;
+            let mut list = repo_handler.list_commits().per_page(100u8).page(page);
+            if let Some(git_ref) = pinned {
+                list = list.sha(git_ref);
+            }
+            let commits = list.send().await?;
+
+            for commit in commits.items.iter() {
+                for co_author in co_author_trailers(&commit.commit.message) {
+                    *co_authors.entry(co_author).or_insert(0) += 1;
+                }
+            }
+
+            if commits.next.is_none() {
+                break;
+            }
+        }
+
+        Ok(co_authors)
+    }
+
+    /// Contributors tallied from commits reachable from the ref, the latest of them
+    /// when there are more than pages of them, or than a single page when shallow
+    async fn commits_up_to(
+        &self,
+        repo_handler: &RepoHandler<'_>,
+        repo_name: &str,
+        git_ref: &str,
+    ) -> anyhow::Result<Vec<Contribution>> {
+        let mut authors = BTreeMap::new();
+        let pages = if self.shallow { 1 } else { PINNED_PAGES };
+
+        for page in 1..=pages {
+            self.reserve().await?;
+            let commits = repo_handler
+                .list_commits()
+                .sha(git_ref)
+                .per_page(100u8)
+                .page(page)
+                .send()
+                .await?;
+
+            for author in commits.items.into_iter().filter_map(|c| c.author) {
+                let (.., count) = authors.entry(author.login).or_insert((
+                    author.html_url.to_string(),
+                    author.avatar_url.to_string(),
+                    0,
+                ));
+                *count += 1;
+            }
+
+            if commits.next.is_none() {
+                break;
+            }
+        }
+
+        Ok(authors
+            .into_iter()
+            .map(|(login, (url, avatar_url, commits))| {
+                (repo_name.to_string(), login, url, avatar_url, commits)
+            })
+            .collect())
+    }
+
+    /// Funding links in .github/FUNDING.yml of the repository, none without one
+    async fn funding(&self, repo_handler: &RepoHandler<'_>) -> anyhow::Result<Vec<String>> {
+        self.reserve().await?;
+        let content = match repo_handler
+            .get_content()
+            .path(".github/FUNDING.yml")
+            .send()
+            .await
+        {
+            Ok(content) => content,
+            Err(octocrab::Error::GitHub { source, .. })
+                if source.status_code == StatusCode::NOT_FOUND =>
+            {
+                return Ok(vec![]);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(content
+            .items
+            .first()
+            .and_then(|item| item.decoded_content())
+            .map(|yml| funding_links(&yml))
+            .unwrap_or_default())
+    }
+
+    /// Commits of each author per month, oldest month first, over the last months,
+    /// of commits up to the ref when pinned
+    async fn history(
+        &self,
+        repo_handler: &RepoHandler<'_>,
+        months: u32,
+        pinned: Option<&str>,
+    ) -> anyhow::Result<BTreeMap<String, Vec<u32>>> {
+        let now = Utc::now();
+        let current = month_index(now);
+        let first = current - months as i32 + 1;
+        let since =
+            NaiveDate::from_ymd_opt(first.div_euclid(12), first.rem_euclid(12) as u32 + 1, 1)
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|d| d.and_utc())
+                .unwrap_or(now);
+        let mut history = BTreeMap::new();
+
+        for page in 1..=HISTORY_PAGES {
+            self.reserve().await?;
+            let mut list = repo_handler
+                .list_commits()
+                .since(since)
+                .per_page(100u8)
+                .page(page);
+            if let Some(git_ref) = pinned {
+                list = list.sha(git_ref);
+            }
+            let commits = list.send().await?;
+
+            for commit in commits.items.iter() {
+                let Some(login) = commit.author.as_ref().map(|a| a.login.clone()) else {
+                    continue;
+                };
+                let Some(date) = commit.commit.author.as_ref().and_then(|a| a.date) else {
+                    continue;
+                };
+                let month = month_index(date) - first;
+                if (0..months as i32).contains(&month) {
+                    let buckets = history
+                        .entry(login)
+                        .or_insert_with(|| vec![0; months as usize]);
+                    buckets[month as usize] += 1;
+                }
+            }
+
+            if commits.next.is_none() {
+                break;
+            }
+        }
+
+        Ok(history)
+    }
+}
+
+/// Months since year zero, so that months of different years can be subtracted
+fn month_index(date: DateTime<Utc>) -> i32 {
+    date.year() * 12 + date.month0() as i32
+}
+
+/// Links of platforms listed in a FUNDING.yml, single names or lists of them,
+/// inline or one per line
+fn funding_links(yml: &str) -> Vec<String> {
+    let link = |platform: &str, name: &str| {
+        let name = name.trim().trim_matches(['"', '\'']);
+        let base = match platform {
+            "github" => "https://github.com/sponsors/",
+            "patreon" => "https://www.patreon.com/",
+            "open_collective" => "https://opencollective.com/",
+            "ko_fi" => "https://ko-fi.com/",
+            "liberapay" => "https://liberapay.com/",
+            "buy_me_a_coffee" => "https://buymeacoffee.com/",
+            "polar" => "https://polar.sh/",
+            "custom" => "",
+            _ => return None,
+        };
+        // ~ and null leave a platform out in yaml
+        (!matches!(name, "" | "~" | "null")).then(|| format!("{base}{name}"))
+    };
+
+    let mut links = vec![];
+    let mut platform = "";
+    for line in yml.lines() {
+        let line = line.split(" #").next().unwrap_or(line).trim();
+        if let Some(item) = line.strip_prefix("- ") {
+            links.extend(link(platform, item));
+        } else if let Some((key, value)) = line.split_once(':').filter(|(k, _)| !k.contains(' ')) {
+            platform = key;
+            let value = value.trim();
+            match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                Some(items) => links.extend(items.split(',').filter_map(|i| link(platform, i))),
+                None => links.extend(link(platform, value)),
+            }
+        }
+    }
+    links
+}
+
+/// Key the organization and funding links of a github repository are cached under by --sponsors
+pub fn sponsors_key(repo: &str) -> String {
+    format!("{repo}, sponsors")
+}
+
+/// Key commit emails of contributors of a gitlab repository are cached under by --unify-by-email
+pub fn emails_key(repo: &str) -> String {
+    format!("{repo}, contributor emails")
+}
+
+/// Github users looked up by the emails they publish on their profiles,
+/// within the search quota shared by all lookups, 30 requests a minute with a token
+pub struct GitHubUsers {
+    client: Octocrab,
+    quota: Mutex<Option<Rate>>,
+}
+
+impl GitHubUsers {
+    pub fn new(client: Octocrab) -> Self {
+        Self {
+            client,
+            quota: Mutex::new(None),
+        }
+    }
+
+    /// Reserves a search request, waiting for the search quota to reset when exhausted
+    async fn reserve(&self) -> anyhow::Result<()> {
+        let mut quota = self.quota.lock().await;
+        let mut current = match quota.take() {
+            Some(rate) => rate,
+            None => self.client.ratelimit().get().await?.resources.search,
+        };
+
+        if current.remaining == 0 {
+            let wait = (current.reset as i64 - Utc::now().timestamp()).max(0) as u64 + 1;
+            status!("{WAITING}waiting {wait}s for github's search rate limit...{WAITING:#}");
+            sleep(Duration::from_secs(wait)).await;
+            current = self.client.ratelimit().get().await?.resources.search;
+        }
+
+        current.remaining = current.remaining.saturating_sub(1);
+        *quota = Some(current);
+        Ok(())
+    }
+
+    /// Login, profile url and avatar url of the github user publishing the email on their profile
+    pub async fn by_email(&self, email: &str) -> anyhow::Result<Option<(String, String, String)>> {
+        self.reserve().await?;
+        let page = self
+            .client
+            .search()
+            .users(&format!("{email} in:email"))
+            .per_page(1)
+            .send()
+            .await?;
+
+        Ok(page.items.into_iter().next().map(|user| {
+            (
+                user.login,
+                user.html_url.to_string(),
+                user.avatar_url.to_string(),
+            )
+        }))
+    }
+}
+
+/// Key monthly commits of a github repository are cached under by --history
+pub fn history_key(repo: &str, months: u32) -> String {
+    format!("{repo}, history {months}")
+}
+
+const STATS_POLLS: usize = 5;
+const STATS_POLL_INTERVAL: Duration = Duration::from_secs(3);
+/// Pages of 100 commits scanned for co-authors
+const CO_AUTHOR_PAGES: u32 = 10;
+/// Pages of 100 commits bucketed into monthly history
+const HISTORY_PAGES: u32 = 50;
+/// Pages of 100 commits tallied for dependencies pinned to a ref
+const PINNED_PAGES: u32 = 50;
+const NOREPLY_DOMAIN: &str = "@users.noreply.github.com";
+
+#[derive(Deserialize)]
+struct StatsContributor {
+    author: Option<StatsAuthor>,
+    total: u32,
+}
+
+#[derive(Deserialize)]
+struct StatsAuthor {
+    login: String,
+    html_url: String,
+    avatar_url: String,
+}
+
+#[async_trait::async_trait]
+impl ContributorSource for GitHub {
+    fn name(&self) -> &str {
+        "github.com"
+    }
+
+    fn cache_key(&self, repo: &str) -> String {
+        // following forks upstream and shallow fetching change what's cached for the source
+        let mut key = repo.to_string();
+        if self.include_forks {
+            key.push_str(", fork");
+        }
+        if self.shallow {
+            key.push_str(", shallow");
+        }
+        if self.stats {
+            key.push_str(", stats");
+        }
+        if self.co_authors {
+            key.push_str(", co-authors");
+        }
+        if let Some(months) = self.history {
+            // history is cached next to contributions, fetching them again refetches it
+            key.push_str(&format!(", history {months}"));
+        }
+        if self.sponsors {
+            key.push_str(", sponsors");
+        }
+        if let Some(git_ref) = self.pinned.get(repo) {
+            key.push_str(&format!(", up to {git_ref}"));
+        }
+        key
+    }
+
+    async fn fetch(&self, repo: &str) -> anyhow::Result<Vec<Contribution>> {
+        let pinned = self.pinned.get(repo).map(String::as_str);
+        let Some((owner, name)) = unformat!("https://github.com/{}/{}", repo) else {
+            anyhow::bail!("failed to parse github url: {repo}");
+        };
+        // split-off any monorepo paths
+        let name = name.split("/").next().unwrap_or(name);
+
+        let mut full_name = (owner.to_string(), name.to_string());
+        let mut repo_handler = self.client.repos(owner, name);
+        self.reserve().await?;
+        let mut data = repo_handler.get().await?;
+
+        // a pinned ref belongs to the fork
+        if !self.include_forks && pinned.is_none() && data.fork.unwrap_or(false) {
+            if let Some(upstream) = data.source.take().or(data.parent.take()) {
+                if let Some(login) = upstream.owner.as_ref().map(|o| o.login.clone()) {
+                    let fork = format!("{owner}/{name}");
+                    let upstream_name = format!("{login}/{}", upstream.name);
+                    if follows_upstream(repo, &fork, &upstream_name).await {
+                        status!("{fork} is a fork, following upstream: {upstream_name}");
+                        repo_handler = self.client.repos(login.clone(), upstream.name.clone());
+                        full_name = (login, upstream.name.clone());
+                        data = *upstream;
+                    }
+                }
+            }
+        }
+        let (owner, name) = &full_name;
+
+        if self.sponsors {
+            let organization = data
+                .owner
+                .as_ref()
+                .filter(|o| o.r#type == "Organization")
+                .map(|o| o.login.clone());
+            let funding = self.funding(&repo_handler).await?;
+            write_cached(&sponsors_key(repo), (&organization, &funding)).await;
+        }
+
+        // statistics count the default branch, pinned refs are tallied from their commits
+        let stats = if self.stats && pinned.is_none() {
+            self.contributor_stats(owner, name).await
+        } else {
+            None
+        };
+
+        let mut contributions = match (pinned, stats) {
+            (Some(git_ref), _) => {
+                status!("counting commits of {owner}/{name} up to {git_ref}...");
+                self.commits_up_to(&repo_handler, &data.name, git_ref)
+                    .await?
+            }
+            (_, Some(stats)) => stats
+                .into_iter()
+                .filter_map(|c| Some((c.author?, c.total)))
+                .map(|(author, total)| {
+                    (
+                        data.name.clone(),
+                        author.login,
+                        author.html_url,
+                        author.avatar_url,
+                        total,
+                    )
+                })
+                .collect(),
+            (None, None) => {
+                if self.stats {
+                    status!(
+                        "statistics of {owner}/{name} aren't available, listing contributors..."
+                    );
+                }
+                self.reserve().await?;
+                self.list_contributors(repo, (owner, name), &repo_handler, &data.name)
+                    .await?
+            }
+        };
+
+        // there are no commits to scan in empty repositories
+        if contributions.is_empty() {
+            return Ok(contributions);
+        }
+
+        if self.co_authors {
+            status!("scanning commits of {owner}/{name} for co-authors...");
+
+            for ((login, url), commits) in self.co_authors(&repo_handler, pinned).await? {
+                match contributions
+                    .iter_mut()
+                    .find(|(_, l, ..)| l.eq_ignore_ascii_case(&login))
+                {
+                    Some((.., c)) => *c += commits,
+                    None => contributions.push((
+                        data.name.clone(),
+                        login,
+                        url,
+                        Default::default(),
+                        commits,
+                    )),
+                }
+            }
+        }
+
+        if let Some(months) = self.history {
+            status!("bucketing commits of {owner}/{name} by month...");
+            let history = self.history(&repo_handler, months, pinned).await?;
+            write_cached(&history_key(repo, months), &history).await;
+        }
+
+        Ok(contributions)
+    }
+}
+
+/// Whether a fork is followed to its upstream, as answered interactively once,
+/// always when not interactive
+async fn follows_upstream(repo: &str, fork: &str, upstream: &str) -> bool {
+    let key = format!("{repo}, follows upstream");
+    if let Some(follows) = read_cached::<bool>(&key).await {
+        return follows;
+    }
+
+    let Some(answer) = tokio::task::block_in_place(|| {
+        prompt(&format!(
+            "{fork} is a fork of {upstream}, follow it upstream? [Y/n]"
+        ))
+    }) else {
+        return true;
+    };
+
+    let follows = !matches!(answer.to_lowercase().as_str(), "n" | "no");
+    write_cached(&key, follows).await;
+    follows
+}
+
+/// Logins and profile urls in `Co-authored-by` trailers of a commit message,
+/// names without urls when emails don't tell the login
+fn co_author_trailers(message: &str) -> Vec<(String, String)> {
+    message
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let (trailer, value) = line.split_once(':')?;
+            if !trailer.eq_ignore_ascii_case("co-authored-by") {
+                return None;
+            }
+
+            let (name, email) = value.split_once('<')?;
+            let email = email.trim_end_matches('>').trim();
+            let name = name.trim();
+
+            // noreply emails are 12345+login@users.noreply.github.com
+            match email.strip_suffix(NOREPLY_DOMAIN) {
+                Some(local) => {
+                    let login = local.split_once('+').map_or(local, |(_, login)| login);
+                    Some((login.to_string(), format!("https://github.com/{login}")))
+                }
+                None if !name.is_empty() => Some((name.to_string(), Default::default())),
+                None => None,
+            }
+        })
+        .collect()
+}
+
+/// Fetches github repositories in batches with a single GraphQL query each,
+/// counting authors of the latest commits on the default branch,
+/// as GraphQL doesn't list contributors
+pub struct GitHubGraphQl {
+    client: Octocrab,
+    include_forks: bool,
+}
+
+impl GitHubGraphQl {
+    pub fn new(client: Octocrab, include_forks: bool) -> Self {
+        Self {
+            client,
+            include_forks,
+        }
+    }
+}
+
+const GRAPHQL_BATCH: usize = 10;
+const GRAPHQL_HISTORY: usize = 100;
+
+#[derive(Deserialize)]
+struct GraphQlResponse {
+    data: Option<BTreeMap<String, Option<GraphQlRepository>>>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQlRepository {
+    name: String,
+    #[serde(default)]
+    is_fork: bool,
+    parent: Option<Box<GraphQlRepository>>,
+    default_branch_ref: Option<GraphQlRef>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlRef {
+    target: GraphQlCommit,
+}
+
+#[derive(Deserialize)]
+struct GraphQlCommit {
+    history: Option<GraphQlHistory>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlHistory {
+    nodes: Vec<GraphQlHistoryCommit>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlHistoryCommit {
+    author: Option<GraphQlAuthor>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQlAuthor {
+    name: Option<String>,
+    user: Option<GraphQlUser>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQlUser {
+    login: String,
+    url: String,
+    avatar_url: String,
+}
+
+impl GraphQlRepository {
+    fn contributions(self) -> Vec<Contribution> {
+        let mut authors: BTreeMap<String, (String, String, u32)> = BTreeMap::new();
+
+        let commits = self
+            .default_branch_ref
+            .and_then(|r| r.target.history)
+            .map(|h| h.nodes)
+            .unwrap_or_default();
+
+        for author in commits.into_iter().filter_map(|c| c.author) {
+            let (login, url, avatar_url) = match author.user {
+                Some(user) => (user.login, user.url, user.avatar_url),
+                None => match author.name {
+                    Some(name) => (name, Default::default(), Default::default()),
+                    None => continue,
+                },
+            };
+            authors.entry(login).or_insert((url, avatar_url, 0)).2 += 1;
+        }
+
+        authors
+            .into_iter()
+            .map(|(login, (url, avatar_url, commits))| {
+                (self.name.clone(), login, url, avatar_url, commits)
+            })
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl ContributorSource for GitHubGraphQl {
+    fn name(&self) -> &str {
+        "github.com"
+    }
+
+    fn cache_key(&self, repo: &str) -> String {
+        let mut key = format!("{repo}, graphql");
+        if self.include_forks {
+            key.push_str(", fork");
+        }
+        key
+    }
+
+    async fn fetch(&self, repo: &str) -> anyhow::Result<Vec<Contribution>> {
+        self.fetch_many(&[repo.to_string()])
+            .await
+            .pop()
+            .unwrap_or_else(|| Ok(vec![]))
+    }
+
+    fn batch_size(&self) -> usize {
+        GRAPHQL_BATCH
+    }
+
+    async fn fetch_many(&self, repos: &[String]) -> Vec<anyhow::Result<Vec<Contribution>>> {
+        let mut fields = vec![];
+
+        for (i, repo) in repos.iter().enumerate() {
+            if let Some((owner, name)) = unformat!("https://github.com/{}/{}", repo.as_str()) {
+                // split-off any monorepo paths, pinned refs are counted at HEAD here
+                let name = name.split('/').next().unwrap_or(name);
+                // json strings are valid graphql string literals
+                fields.push(format!(
+                    "r{i}: repository(owner: {}, name: {}) {{ isFork parent {{ ...contributors }} ...contributors }}",
+                    serde_json::Value::from(owner),
+                    serde_json::Value::from(name)
+                ));
+            }
+        }
+
+        let query = format!(
+            "fragment contributors on Repository {{ name defaultBranchRef {{ target {{ ... on Commit {{ history(first: {GRAPHQL_HISTORY}) {{ nodes {{ author {{ name user {{ login url avatarUrl }} }} }} }} }} }} }} }}\nquery {{ {} }}",
+            fields.join(" ")
+        );
+
+        let response = self
+            .client
+            .graphql::<GraphQlResponse>(&serde_json::json!({ "query": query }))
+            .await;
+
+        let mut data = match response {
+            Ok(GraphQlResponse {
+                data: Some(data), ..
+            }) => data,
+            Ok(GraphQlResponse { errors, .. }) => {
+                let message = Vec::from_iter(errors.into_iter().map(|e| e.message)).join(", ");
+                return repos
+                    .iter()
+                    .map(|_| Err(anyhow::anyhow!("graphql query failed: {message}")))
+                    .collect();
+            }
+            Err(e) => {
+                let message = e.to_string();
+                return repos
+                    .iter()
+                    .map(|_| Err(anyhow::anyhow!("graphql query failed: {message}")))
+                    .collect();
+            }
+        };
+
+        repos
+            .iter()
+            .enumerate()
+            .map(|(i, repo)| {
+                let mut data = data
+                    .remove(&format!("r{i}"))
+                    .flatten()
+                    .ok_or_else(|| anyhow::anyhow!("failed to query github repository: {repo}"))?;
+
+                if !self.include_forks && data.is_fork {
+                    if let Some(upstream) = data.parent.take() {
+                        status!("{repo} is a fork, following upstream: {}", upstream.name);
+                        data = *upstream;
+                    }
+                }
+
+                Ok(data.contributions())
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct GitLabContributor {
+    name: String,
+    #[serde(default)]
+    email: String,
+    commits: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GitLabProject {
+    name: String,
+}
+
+pub struct GitLab {
+    client: reqwest::Client,
+    /// Base url all instances are reached at instead of their own host
+    api: Option<String>,
+    concurrency: usize,
+    emails: bool,
+}
+
+impl GitLab {
+    pub fn new(
+        client: reqwest::Client,
+        api: Option<String>,
+        concurrency: usize,
+        emails: bool,
+    ) -> Self {
+        Self {
+            client,
+            api,
+            concurrency,
+            emails,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ContributorSource for GitLab {
+    fn name(&self) -> &str {
+        "gitlab"
+    }
+
+    fn cache_key(&self, repo: &str) -> String {
+        // emails are cached along with contributions
+        if self.emails {
+            format!("{repo}, emails")
+        } else {
+            repo.to_string()
+        }
+    }
+
+    fn batch_size(&self) -> usize {
+        self.concurrency
+    }
+
+    async fn fetch(&self, repo: &str) -> anyhow::Result<Vec<Contribution>> {
+        let Some((base, project)) = gitlab_project(repo) else {
+            anyhow::bail!("failed to parse gitlab url: {repo}");
+        };
+
+        let base = match &self.api {
+            Some(api) => api.clone(),
+            None => format!("https://{base}"),
+        };
+        let url = format!("{base}/api/v4/projects/{project}");
+        let data = client::get(&self.client, &url)
+            .await?
+            .error_for_status()?
+            .json::<GitLabProject>()
+            .await?;
+        let url = format!("{url}/repository/contributors");
+        let contributors = client::get(&self.client, &url)
+            .await?
+            .error_for_status()?
+            .json::<Vec<GitLabContributor>>()
+            .await?;
+
+        if self.emails {
+            let emails = BTreeMap::from_iter(
+                contributors
+                    .iter()
+                    .filter(|c| !c.email.is_empty())
+                    .map(|c| (c.name.clone(), c.email.clone())),
+            );
+            write_cached(&emails_key(repo), &emails).await;
+        }
+
+        Ok(contributors
+            .into_iter()
+            .map(|c| {
+                (
+                    data.name.clone(),
+                    c.name,
+                    // TODO: get to user page url...
+                    Default::default(),
+                    Default::default(),
+                    c.commits,
+                )
+            })
+            .collect())
+    }
+}
+
+/// Host and url-encoded path of a gitlab project,
+/// projects can be nested in any depth of subgroups, so only `/-/` ends the path
+fn gitlab_project(repo: &str) -> Option<(&str, String)> {
+    let (base, path) = repo.strip_prefix("https://")?.split_once('/')?;
+    let path = path.split("/-/").next().unwrap_or(path).trim_matches('/');
+
+    if !path.contains('/') {
+        return None;
+    }
+
+    Some((base, path.replace('/', "%2F")))
+}
+
+pub struct SourceHut {
+    client: reqwest::Client,
+    concurrency: usize,
+}
+
+impl SourceHut {
+    pub fn new(client: reqwest::Client, concurrency: usize) -> Self {
+        Self {
+            client,
+            concurrency,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ContributorSource for SourceHut {
+    fn name(&self) -> &str {
+        "sourcehut"
+    }
+
+    fn batch_size(&self) -> usize {
+        self.concurrency
+    }
+
+    async fn fetch(&self, repo: &str) -> anyhow::Result<Vec<Contribution>> {
+        let Some((base, owner, name)) = unformat!("https://{}/{}/{}", repo) else {
+            anyhow::bail!("failed to parse sourcehut url: {repo}");
+        };
+        let name = name.split("/").next().unwrap_or(name);
+
+        // sourcehut api requires oauth even for public repos,
+        // the log feed is public and lists recent commit authors
+        let url = format!("https://{base}/{owner}/{name}/log/rss.xml");
+        let rss = client::get(&self.client, &url)
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        Ok(rss_authors(rss.as_str())
+            .into_iter()
+            .map(|(author, commits)| {
+                (
+                    name.to_string(),
+                    author,
+                    Default::default(),
+                    Default::default(),
+                    commits,
+                )
+            })
+            .collect())
+    }
+}
+
+/// User provided command for any other forge
+pub struct Resolver {
+    command: String,
+}
+
+impl Resolver {
+    pub fn new(command: String) -> Self {
+        Self { command }
+    }
+}
+
+#[derive(Deserialize)]
+struct ResolvedContributor {
+    name: String,
+    #[serde(default)]
+    profile_url: String,
+    commits: u32,
+}
+
+#[async_trait::async_trait]
+impl ContributorSource for Resolver {
+    fn name(&self) -> &str {
+        "resolver"
+    }
+
+    fn cache_key(&self, repo: &str) -> String {
+        format!("{repo}, resolver")
+    }
+
+    async fn fetch(&self, repo: &str) -> anyhow::Result<Vec<Contribution>> {
+        let command = self.command.clone();
+        let input = repo.to_string();
+
+        let output = tokio::task::spawn_blocking(move || {
+            let mut cmd = if cfg!(windows) {
+                let mut cmd = Command::new("cmd");
+                cmd.args(["/C", &command]);
+                cmd
+            } else {
+                let mut cmd = Command::new("sh");
+                cmd.args(["-c", &command]);
+                cmd
+            };
+            let mut child = cmd
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(input.as_bytes())?;
+            }
+
+            child.wait_with_output()
+        })
+        .await??;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "source resolver exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        // a broken resolver shouldn't be remembered as a failed source
+        let contributors: Vec<ResolvedContributor> = serde_json::from_slice(&output.stdout)
+            .map_err(|e| anyhow::anyhow!("unexpected output of source resolver: {e}"))?;
+
+        let name = repo.rsplit('/').next().unwrap_or(repo);
+
+        Ok(contributors
+            .into_iter()
+            .map(|c| {
+                (
+                    name.to_string(),
+                    c.name,
+                    c.profile_url,
+                    Default::default(),
+                    c.commits,
+                )
+            })
+            .collect())
+    }
+}
+
+/// Local clones in a directory, named after their repositories, tallied with `git shortlog`
+pub struct LocalRepos {
+    dir: PathBuf,
+}
+
+impl LocalRepos {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Clone of the repository in the directory, if there's one, at `DIR/owner/name`,
+    /// or at `DIR/name` when its origin is the repository, as names alone are ambiguous.
+    /// Subpaths of monorepos are found in a clone of the whole repository
+    fn clone_path(&self, repo: &str) -> Option<PathBuf> {
+        let repo = repo.trim_end_matches('/');
+        let (_, path) = repo.split_once("://")?.1.split_once('/')?;
+        let segments = Vec::from_iter(path.split('/'));
+
+        let nested = (2..=segments.len())
+            .rev()
+            .map(|depth| self.dir.join(segments[..depth].join("/")))
+            .find(|path| path.join(".git").exists());
+        if nested.is_some() {
+            return nested;
+        }
+
+        segments[1..]
+            .iter()
+            .map(|name| self.dir.join(name))
+            .filter(|path| path.join(".git").exists())
+            .find(|path| {
+                origin_url(path).is_some_and(|origin| {
+                    repo == origin
+                        || repo
+                            .strip_prefix(origin.as_str())
+                            .is_some_and(|rest| rest.starts_with('/'))
+                })
+            })
+    }
+
+    pub fn has_clone(&self, repo: &str) -> bool {
+        self.clone_path(repo).is_some()
+    }
+}
+
+/// Normalized url of the origin remote of a clone
+fn origin_url(path: &std::path::Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .args(["config", "--get", "remote.origin.url"])
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| crate::normalize_git_url(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[async_trait::async_trait]
+impl ContributorSource for LocalRepos {
+    fn name(&self) -> &str {
+        "local clone"
+    }
+
+    fn cache_key(&self, repo: &str) -> String {
+        format!("{repo}, local")
+    }
+
+    async fn fetch(&self, repo: &str) -> anyhow::Result<Vec<Contribution>> {
+        let Some(path) = self.clone_path(repo) else {
+            anyhow::bail!("no local clone of {repo} in {}", self.dir.display());
+        };
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let output = tokio::task::spawn_blocking(move || {
+            Command::new("git")
+                .arg("-C")
+                .arg(&path)
+                .args(["shortlog", "-sn", "HEAD"])
+                .stdin(Stdio::null())
+                .output()
+        })
+        .await??;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "git shortlog exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(shortlog_authors(&String::from_utf8_lossy(&output.stdout))
+            .into_iter()
+            .map(|(author, commits)| {
+                (
+                    name.clone(),
+                    author,
+                    Default::default(),
+                    Default::default(),
+                    commits,
+                )
+            })
+            .collect())
+    }
+}
+
+/// Authors and their commits in `git shortlog -sn` output
+fn shortlog_authors(output: &str) -> Vec<(String, u32)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (commits, author) = line.trim().split_once('\t')?;
+            Some((author.trim().to_string(), commits.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Whether a fetch failed for good, e.g. a missing repository or unexpected data,
+/// rather than for network trouble or limits that may pass
+pub fn is_permanent(e: &anyhow::Error) -> bool {
+    let gone = |status| matches!(status, StatusCode::NOT_FOUND | StatusCode::GONE);
+
+    e.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            e.status().is_some_and(gone)
+        } else if let Some(octocrab::Error::GitHub { source, .. }) =
+            cause.downcast_ref::<octocrab::Error>()
+        {
+            gone(source.status_code)
+        } else {
+            cause.is::<serde_json::Error>()
+        }
+    })
+}
+
+async fn gh_rate_limited(mut limit: RateLimit, client: &Octocrab) -> anyhow::Result<RateLimit> {
+    if limit.resources.core.remaining > 0 {
+        limit.resources.core.remaining -= 1;
+        anyhow::Ok(limit)
+    } else {
+        let timeout = DateTime::<Utc>::from_timestamp(limit.resources.core.reset as i64, 0)
+            .expect("create timeout");
+        let now = Utc::now();
+        let duration = timeout.signed_duration_since(now);
+        let seconds = duration.num_seconds() as u64;
+        for _ in 1..=seconds {
+            let now = Utc::now();
+            let duration = timeout.signed_duration_since(now);
+            let message = format!("\r{WAITING}Honouring your contributors {} requests were made, now please honour github's rate limit, and wait kindly {:0>2}m {:0>2}s...{WAITING:#}",
+                limit.resources.core.limit,
+                duration.num_minutes(),
+                duration.num_seconds() - duration.num_minutes() * 60,
+            );
+            if STATUS_TO_STDERR.load(std::sync::atomic::Ordering::Relaxed) {
+                _ = write!(anstream::stderr(), "{message}");
+            } else {
+                let mut stdout = anstream::stdout();
+                _ = write!(stdout, "{message}");
+                stdout.flush().unwrap();
+            }
+
+            sleep(Duration::from_secs(1)).await;
+        }
+        let mut new_limit = client.ratelimit().get().await?;
+        new_limit.resources.core.limit += limit.resources.core.limit;
+        anyhow::Ok(new_limit)
+    }
+}
+
+/// Counts commits per author name in a sourcehut log feed, where authors are formatted
+/// as `email (Name)`, authors without a name are skipped so that emails aren't published.
+/// The feed covers only the latest commits, so counts are partial
+fn rss_authors(rss: &str) -> BTreeMap<String, u32> {
+    let mut authors = BTreeMap::new();
+
+    for item in rss.split("<author>").skip(1) {
+        let Some(name) = item
+            .split("</author>")
+            .next()
+            .and_then(|author| author.split_once(" ("))
+            .and_then(|(_, name)| name.strip_suffix(')'))
+        else {
+            continue;
+        };
+        let name = name
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&amp;", "&");
+        *authors.entry(name.trim().to_string()).or_insert(0) += 1;
+    }
+
+    authors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_named_rss_authors_only() {
+        let rss = "<item><author>erin@example.com (Erin &amp; co)</author></item>\
+                   <item><author>erin@example.com (Erin &amp; co)</author></item>\
+                   <item><author>anon@example.com</author></item>";
+
+        assert_eq!(
+            rss_authors(rss),
+            BTreeMap::from([("Erin & co".to_string(), 2)])
+        );
+    }
+
+    #[test]
+    fn finds_clones_by_owner_or_origin() {
+        let dir = std::env::temp_dir().join(format!("acknowledge-clones-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        let git = |path: PathBuf, origin: &str| {
+            std::fs::create_dir_all(&path).unwrap();
+            for args in [vec!["init", "-q"], vec!["remote", "add", "origin", origin]] {
+                let status = Command::new("git")
+                    .arg("-C")
+                    .arg(&path)
+                    .args(args)
+                    .status()
+                    .unwrap();
+                assert!(status.success());
+            }
+        };
+        git(dir.join("a/utils"), "https://github.com/a/utils");
+        git(dir.join("mono"), "git@github.com:b/mono.git");
+        git(dir.join("utils"), "https://github.com/c/utils");
+
+        let local = LocalRepos::new(dir.clone());
+        let clone = |repo: &str| {
+            local
+                .clone_path(repo)
+                .map(|p| p.strip_prefix(&dir).unwrap().to_path_buf())
+        };
+
+        assert_eq!(clone("https://github.com/a/utils"), Some("a/utils".into()));
+        assert_eq!(clone("https://github.com/c/utils"), Some("utils".into()));
+        assert_eq!(clone("https://github.com/d/utils"), None);
+        assert_eq!(
+            clone("https://github.com/b/mono/tree/main/crates/x"),
+            Some("mono".into())
+        );
+        assert_eq!(clone("https://github.com/e/x"), None);
+
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn parses_gitlab_subgroups() {
+        assert_eq!(
+            gitlab_project("https://gitlab.com/group/repo"),
+            Some(("gitlab.com", "group%2Frepo".to_string()))
+        );
+        assert_eq!(
+            gitlab_project("https://gitlab.com/group/subgroup/nested/repo"),
+            Some(("gitlab.com", "group%2Fsubgroup%2Fnested%2Frepo".to_string()))
+        );
+        assert_eq!(
+            gitlab_project("https://gitlab.example.com/group/subgroup/repo/-/tree/main/crates/foo"),
+            Some(("gitlab.example.com", "group%2Fsubgroup%2Frepo".to_string()))
+        );
+        assert_eq!(gitlab_project("https://gitlab.com/group"), None);
+    }
+
+    #[test]
+    fn parses_funding_links() {
+        let yml = "github: [alice, \"bob\"]\npatreon: carol # monthly\nko_fi: ~\ncustom:\n  - https://example.com/donate\n";
+
+        assert_eq!(
+            funding_links(yml),
+            vec![
+                "https://github.com/sponsors/alice",
+                "https://github.com/sponsors/bob",
+                "https://www.patreon.com/carol",
+                "https://example.com/donate",
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_shortlog_authors() {
+        assert_eq!(
+            shortlog_authors("   120\tAlice\n     3\tBob Smith\nnot a line\n"),
+            vec![("Alice".to_string(), 120), ("Bob Smith".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn parses_co_author_trailers() {
+        let message = "Pair on parser\n\nCo-authored-by: Alice <1234+alice@users.noreply.github.com>\nco-authored-by: Bob Smith <bob@example.com>\nSigned-off-by: Carol <carol@example.com>";
+
+        assert_eq!(
+            co_author_trailers(message),
+            vec![
+                ("alice".to_string(), "https://github.com/alice".to_string()),
+                ("Bob Smith".to_string(), String::new()),
+            ]
+        );
+    }
+}
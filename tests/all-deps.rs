@@ -10,7 +10,7 @@ fn generate_with_dep_and_names() {
     out.push("ACKNOWLEDGEMENTS-BuildAndDev.md");
     let output = Command::new(path)
         .arg(format!("-p={dot}"))
-        .arg(format!("-b=BuildAndDev"))
+        .arg("-b=BuildAndDev")
         .arg(format!("--output={}", out.to_str().unwrap()))
         .output()
         .expect("Failed to run");
@@ -7,7 +7,7 @@ fn print_help() {
     let mut path = PathBuf::from(&dot);
     path.push("target/debug/acknowledge");
     let output = Command::new(path)
-        .arg(format!("--help"))
+        .arg("--help")
         .output()
         .expect("Failed to run");
     let printed = String::from_utf8(output.stdout).expect("Failed to parse");
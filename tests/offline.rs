@@ -0,0 +1,459 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Renders the fixture project from a cache seeded with fixture data,
+/// so that no crates.io, github, gitlab or sourcehut requests are made,
+/// and from dependency manifests in a fixture cargo registry
+fn generate(name: &str, args: &[&str]) -> String {
+    generate_fixture("project", name, args)
+}
+
+/// Renders one of the fixture projects like `generate`
+fn generate_fixture(fixture: &str, name: &str, args: &[&str]) -> String {
+    let (path, project, cache_dir) = seed_cache(fixture, name);
+
+    let output = Command::new(&path)
+        .args(["-p", &project, "--cache-dir", &cache_dir, "-o", "-"])
+        .args(args)
+        .env("CARGO_HOME", cargo_home())
+        .output()
+        .expect("Failed to run");
+    assert!(output.status.success(), "generate failed: {output:#?}");
+
+    String::from_utf8(output.stdout).expect("Failed to parse")
+}
+
+/// Imports fixture data into a cache dir of its own,
+/// returning the binary, the fixture project and the cache dir
+fn seed_cache(fixture: &str, name: &str) -> (PathBuf, String, String) {
+    let dot = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let mut path = PathBuf::from(&dot);
+    path.push("target/debug/acknowledge");
+    let project = format!("{dot}/tests/fixtures/{fixture}");
+    let cache = format!("{dot}/tests/fixtures/cache.json");
+    let cache_dir = format!("{}/{name}", env!("CARGO_TARGET_TMPDIR"));
+    _ = std::fs::remove_dir_all(&cache_dir);
+
+    let import = Command::new(&path)
+        .args(["-p", &project, "--cache-dir", &cache_dir])
+        .args(["import-cache", &cache])
+        .output()
+        .expect("Failed to run");
+    assert!(import.status.success(), "import failed: {import:#?}");
+
+    (path, project, cache_dir)
+}
+
+fn cargo_home() -> String {
+    format!(
+        "{}/tests/fixtures/cargo_home",
+        std::env::var("CARGO_MANIFEST_DIR").unwrap()
+    )
+}
+
+#[test]
+fn offline_name_and_count() {
+    insta::assert_snapshot!(generate("name_and_count", &["-c", "2"]));
+}
+
+#[test]
+fn offline_dep_and_names() {
+    insta::assert_snapshot!(generate("dep_and_names", &["-c", "2", "-f", "DepAndNames"]));
+}
+
+#[test]
+fn offline_name_and_deps() {
+    insta::assert_snapshot!(generate("name_and_deps", &["-c", "2", "-f", "NameAndDeps"]));
+}
+
+#[test]
+fn offline_group_bots_separately() {
+    insta::assert_snapshot!(generate(
+        "group_bots_separately",
+        &["-c", "2", "--group-bots-separately"]
+    ));
+}
+
+#[test]
+fn offline_no_emoji() {
+    insta::assert_snapshot!(generate(
+        "no_emoji",
+        &["-c", "2", "--group-bots-separately", "--no-emoji"]
+    ));
+}
+
+#[test]
+fn offline_sort_name() {
+    insta::assert_snapshot!(generate("sort_name", &["-c", "2", "--sort", "name"]));
+}
+
+#[test]
+fn offline_name_and_deps_sort_name() {
+    insta::assert_snapshot!(generate(
+        "name_and_deps_sort_name",
+        &["-c", "2", "-f", "NameAndDeps", "--sort", "name"]
+    ));
+}
+
+#[test]
+fn offline_percentages() {
+    insta::assert_snapshot!(generate("percentages", &["-c", "2", "--percentages"]));
+}
+
+#[test]
+fn offline_versions() {
+    insta::assert_snapshot!(generate(
+        "versions",
+        &["-c", "2", "-f", "DepAndNames", "--versions"]
+    ));
+}
+
+#[test]
+fn offline_category_and_names() {
+    insta::assert_snapshot!(generate(
+        "category_and_names",
+        &["-c", "2", "-f", "CategoryAndNames"]
+    ));
+}
+
+#[test]
+fn offline_template_dir() {
+    let dot = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    insta::assert_snapshot!(generate(
+        "template_dir",
+        &[
+            "-c",
+            "2",
+            "--template-dir",
+            &format!("{dot}/tests/fixtures/template_dir")
+        ]
+    ));
+}
+
+#[test]
+fn offline_crate_sort_downloads() {
+    insta::assert_snapshot!(generate(
+        "crate_sort_downloads",
+        &["-c", "2", "-f", "DepAndNames", "--crate-sort", "downloads"]
+    ));
+}
+
+#[test]
+fn offline_licenses() {
+    insta::assert_snapshot!(generate("licenses", &["--licenses"]));
+}
+
+#[test]
+fn offline_others_tail_pct() {
+    insta::assert_snapshot!(generate(
+        "others_tail_pct",
+        &["-c", "1", "--others-tail-pct", "10"]
+    ));
+}
+
+#[test]
+fn offline_rst() {
+    insta::assert_snapshot!(generate(
+        "rst",
+        &["-c", "2", "-f", "DepAndNames", "--markup", "rst"]
+    ));
+}
+
+#[test]
+fn offline_unify_by_email() {
+    insta::assert_snapshot!(generate("unify_by_email", &["-c", "2", "--unify-by-email"]));
+}
+
+#[test]
+fn offline_adaptive_factor() {
+    insta::assert_snapshot!(generate(
+        "adaptive_factor",
+        &["-c", "2", "-f", "DepAndNames", "--adaptive-factor", "0.2"]
+    ));
+}
+
+#[test]
+fn offline_workspace_inheritance() {
+    insta::assert_snapshot!(generate_fixture(
+        "workspace",
+        "workspace_inheritance",
+        &["-c", "2", "-f", "NameAndDeps"]
+    ));
+}
+
+#[test]
+fn offline_workspace_package() {
+    insta::assert_snapshot!(generate_fixture(
+        "workspace",
+        "workspace_package",
+        &["-c", "2", "-f", "NameAndDeps", "--package", "lib"]
+    ));
+}
+
+#[test]
+fn offline_min_crate_contributors() {
+    insta::assert_snapshot!(generate(
+        "min_crate_contributors",
+        &[
+            "-c",
+            "2",
+            "-f",
+            "DepAndNames",
+            "--min-crate-contributors",
+            "2"
+        ]
+    ));
+}
+
+#[test]
+fn offline_max_listed() {
+    insta::assert_snapshot!(generate("max_listed", &["-c", "2", "--max-listed", "3"]));
+}
+
+#[test]
+fn offline_manifest_on_stdin() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let from_path = generate("manifest_on_stdin", &["-c", "2"]);
+
+    // the cache imported for the project is reused
+    let dot = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let project = format!("{dot}/tests/fixtures/project");
+    let cache_dir = format!("{}/manifest_on_stdin", env!("CARGO_TARGET_TMPDIR"));
+    let mut child = Command::new(format!("{dot}/target/debug/acknowledge"))
+        .args(["-p", "-", "--cache-dir", &cache_dir, "-o", "-", "-c", "2"])
+        .env("CARGO_HOME", cargo_home())
+        .current_dir(&project)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run");
+    let manifest = std::fs::read(format!("{project}/Cargo.toml")).unwrap();
+    child.stdin.take().unwrap().write_all(&manifest).unwrap();
+    let output = child.wait_with_output().expect("Failed to run");
+    assert!(output.status.success(), "generate failed: {output:#?}");
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), from_path);
+}
+
+#[test]
+fn offline_check_licenses() {
+    let (path, project, cache_dir) = seed_cache("project", "check_licenses");
+    let licenses = format!("{cache_dir}/LICENSES.md");
+    let run = |check: bool| {
+        Command::new(&path)
+            .args(["-p", &project, "--cache-dir", &cache_dir, "--licenses"])
+            .args(["-o", &licenses])
+            .args(check.then_some("check"))
+            .env("CARGO_HOME", cargo_home())
+            .output()
+            .expect("Failed to run")
+    };
+
+    assert!(!run(true).status.success(), "missing licenses passed check");
+    assert!(
+        !std::path::Path::new(&licenses).exists(),
+        "check wrote licenses"
+    );
+    assert!(run(false).status.success());
+    assert!(run(true).status.success(), "fresh licenses failed check");
+}
+
+#[test]
+fn offline_toc() {
+    insta::assert_snapshot!(generate("toc", &["-c", "2", "-f", "DepAndNames", "--toc"]));
+}
+
+#[test]
+fn offline_toc_rst() {
+    insta::assert_snapshot!(generate(
+        "toc_rst",
+        &["-c", "2", "-f", "DepAndNames", "--toc", "--markup", "rst"]
+    ));
+}
+
+#[test]
+fn offline_timing_file() {
+    let timing = format!("{}/timing_file.json", env!("CARGO_TARGET_TMPDIR"));
+    generate("timing_file", &["--timing-file", &timing]);
+
+    let timings: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(timing).unwrap()).unwrap();
+    assert!(timings
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|t| t["phase"] == "rendering"));
+}
+
+/// Serves json bodies by request path on a local port, standing in for the github api,
+/// returning its base url
+fn serve(routes: Vec<(&'static str, serde_json::Value)>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut head = String::new();
+            for line in BufReader::new(&stream).lines() {
+                let line = line.unwrap_or_default();
+                if line.is_empty() {
+                    break;
+                }
+                head.push_str(&line);
+                head.push('\n');
+            }
+            let path = head.split(' ').nth(1).unwrap_or_default();
+            let (status, body) = match routes.iter().find(|(route, _)| *route == path) {
+                Some((_, body)) => ("200 OK", body.to_string()),
+                None => ("404 Not Found", r#"{"message":"Not Found"}"#.to_string()),
+            };
+            _ = write!(
+                stream,
+                "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+        }
+    });
+
+    base
+}
+
+/// A github user as listed among contributors and commit authors
+fn github_user(login: &str) -> serde_json::Value {
+    let url = format!("https://api.github.com/users/{login}");
+    serde_json::json!({
+        "login": login,
+        "id": 1,
+        "node_id": "",
+        "avatar_url": format!("https://avatars.githubusercontent.com/{login}"),
+        "gravatar_id": "",
+        "url": url,
+        "html_url": format!("https://github.com/{login}"),
+        "followers_url": url,
+        "following_url": url,
+        "gists_url": url,
+        "starred_url": url,
+        "subscriptions_url": url,
+        "organizations_url": url,
+        "repos_url": url,
+        "events_url": url,
+        "received_events_url": url,
+        "type": "User",
+        "site_admin": false,
+    })
+}
+
+#[test]
+fn offline_github_stub() {
+    let rate = serde_json::json!({ "limit": 60, "used": 0, "remaining": 60, "reset": 0 });
+    let repo = |name: &str| serde_json::json!({ "id": 1, "name": name, "url": format!("https://api.github.com/repos/stub/{name}") });
+    let contributor = |login: &str, contributions: u32| {
+        let mut user = github_user(login);
+        user["contributions"] = contributions.into();
+        user
+    };
+    let commit = |login: &str| {
+        serde_json::json!({
+            "sha": "0",
+            "node_id": "",
+            "url": "https://api.github.com/repos/stub/qux/commits/0",
+            "html_url": "https://github.com/stub/qux/commit/0",
+            "comments_url": "https://api.github.com/repos/stub/qux/commits/0/comments",
+            "commit": {
+                "url": "https://api.github.com/repos/stub/qux/git/commits/0",
+                "message": "change",
+                "comment_count": 0,
+                "tree": { "sha": "0", "url": "https://api.github.com/repos/stub/qux/git/trees/0" },
+            },
+            "author": github_user(login),
+            "parents": [],
+        })
+    };
+    let api = serve(vec![
+        (
+            "/rate_limit",
+            serde_json::json!({ "resources": { "core": rate, "search": rate }, "rate": rate }),
+        ),
+        ("/repos/stub/foo", repo("foo")),
+        (
+            "/repos/stub/foo/contributors",
+            serde_json::json!([contributor("alice", 5), contributor("bob", 3)]),
+        ),
+        ("/repos/stub/qux", repo("qux")),
+        (
+            "/repos/stub/qux/commits?sha=v1&per_page=100&page=1",
+            serde_json::json!([commit("carol"), commit("carol"), commit("alice")]),
+        ),
+    ]);
+
+    let (path, project, cache_dir) = seed_cache("github", "github_stub");
+    let output = Command::new(&path)
+        .args(["-p", &project, "--cache-dir", &cache_dir, "-o", "-"])
+        .args(["-c", "1", "-f", "DepAndNames"])
+        .env("CARGO_HOME", cargo_home())
+        .env("ACKNOWLEDGE_GITHUB_API", api)
+        .output()
+        .expect("Failed to run");
+    assert!(output.status.success(), "generate failed: {output:#?}");
+
+    insta::assert_snapshot!(String::from_utf8(output.stdout).expect("Failed to parse"));
+}
+
+#[test]
+fn offline_crates_io_and_gitlab_stub() {
+    let api = serve(vec![
+        (
+            "/crates/quux",
+            serde_json::json!({
+                "crate": {
+                    "repository": "https://gitlab.com/stub/group/quux",
+                    "downloads": 100,
+                    "max_version": "1.1.0",
+                },
+                "categories": [{ "category": "Parsing" }],
+                "versions": [
+                    { "num": "1.1.0", "license": "MIT" },
+                    { "num": "1.0.0", "license": "Apache-2.0" },
+                ],
+            }),
+        ),
+        (
+            "/crates?q=nope&per_page=3",
+            serde_json::json!({ "crates": [{ "name": "nopes" }] }),
+        ),
+        (
+            "/api/v4/projects/stub%2Fgroup%2Fquux",
+            serde_json::json!({ "name": "quux" }),
+        ),
+        (
+            "/api/v4/projects/stub%2Fgroup%2Fquux/repository/contributors",
+            serde_json::json!([
+                { "name": "Dana", "email": "dana@example.com", "commits": 4 },
+                { "name": "Erin", "commits": 2 },
+            ]),
+        ),
+    ]);
+
+    let (path, project, cache_dir) = seed_cache("crates_io", "crates_io_stub");
+    let output = Command::new(&path)
+        .args(["-p", &project, "--cache-dir", &cache_dir, "-o", "-"])
+        .args(["-c", "1", "-f", "DepAndNames"])
+        .env("CARGO_HOME", cargo_home())
+        .env("ACKNOWLEDGE_CRATES_IO_API", &api)
+        .env("ACKNOWLEDGE_GITLAB_API", &api)
+        .output()
+        .expect("Failed to run");
+    assert!(output.status.success(), "generate failed: {output:#?}");
+    let stderr = String::from_utf8(output.stderr).expect("Failed to parse");
+    assert!(
+        stderr.contains("nope not found on crates.io, skipping, similar crates: nopes"),
+        "{stderr}"
+    );
+
+    insta::assert_snapshot!(String::from_utf8(output.stdout).expect("Failed to parse"));
+}